@@ -3,8 +3,6 @@ use chrono::{DateTime, TimeZone, Utc};
 use serde;
 use serde::de::{Deserialize, Deserializer, Error as DeError};
 use serde::ser::Serializer;
-use std;
-use std::collections::HashMap;
 
 fn normalize_timestamp<'de, D>(deserializer: D) -> Result<(u64, u64), D::Error>
 where
@@ -122,20 +120,282 @@ where
     }
 }
 
-/// Deserializes `HashMap<_>`, mapping JSON `null` to an empty map.
-pub(crate) fn deserialize_lambda_map<'de, D, K, V>(
-    deserializer: D,
-) -> Result<HashMap<K, V>, D::Error>
+/// Deserializes `Option<Box<str>>`, mapping JSON `null` or the empty string `""` to `None`.
+#[allow(dead_code)]
+pub(crate) fn deserialize_lambda_boxed_str<'de, D>(deserializer: D) -> Result<Option<Box<str>>, D::Error>
 where
     D: Deserializer<'de>,
-    K: serde::Deserialize<'de>,
-    K: std::hash::Hash,
-    K: std::cmp::Eq,
-    V: serde::Deserialize<'de>,
+{
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    Ok(s.filter(|s| !s.is_empty()).map(String::into_boxed_str))
+}
+
+/// Deserializes a map like `HashMap<_>`, mapping JSON `null` to an empty map.
+pub(crate) fn deserialize_lambda_map<'de, D, M>(deserializer: D) -> Result<M, D::Error>
+where
+    D: Deserializer<'de>,
+    M: serde::Deserialize<'de> + Default,
 {
     // https://github.com/serde-rs/serde/issues/1098
     let opt = Option::deserialize(deserializer)?;
-    Ok(opt.unwrap_or(HashMap::default()))
+    Ok(opt.unwrap_or_default())
+}
+
+/// Deserializes `bool`, also accepting `"true"`/`"false"` and `1`/`0`.
+#[allow(dead_code)]
+pub(crate) fn deserialize_lambda_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrStringOrNumber {
+        Bool(bool),
+        String(String),
+        Int(u64),
+    }
+
+    match BoolOrStringOrNumber::deserialize(deserializer)? {
+        BoolOrStringOrNumber::Bool(b) => Ok(b),
+        BoolOrStringOrNumber::String(ref s) if s == "true" => Ok(true),
+        BoolOrStringOrNumber::String(ref s) if s == "false" => Ok(false),
+        BoolOrStringOrNumber::Int(1) => Ok(true),
+        BoolOrStringOrNumber::Int(0) => Ok(false),
+        BoolOrStringOrNumber::String(s) => Err(DeError::custom(format!("invalid boolean: {}", s))),
+        BoolOrStringOrNumber::Int(i) => Err(DeError::custom(format!("invalid boolean: {}", i))),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IntegerOrFloatOrString {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+}
+
+impl IntegerOrFloatOrString {
+    // Strings are parsed like the number they contain.
+    fn unquote<E: DeError>(self) -> Result<Self, E> {
+        match self {
+            IntegerOrFloatOrString::String(s) => {
+                let s = s.trim();
+                if let Ok(i) = s.parse::<i64>() {
+                    Ok(IntegerOrFloatOrString::Int(i))
+                } else if let Ok(u) = s.parse::<u64>() {
+                    Ok(IntegerOrFloatOrString::UInt(u))
+                } else {
+                    s.parse::<f64>()
+                        .map(IntegerOrFloatOrString::Float)
+                        .map_err(|_| DeError::custom(format!("invalid integer: {}", s)))
+                }
+            }
+            other => Ok(other),
+        }
+    }
+}
+
+/// Deserializes `i64`, also accepting numeric strings and truncating floats.
+#[allow(dead_code)]
+pub(crate) fn deserialize_lambda_i64<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match IntegerOrFloatOrString::deserialize(deserializer)?.unquote()? {
+        IntegerOrFloatOrString::Int(i) => Ok(i),
+        IntegerOrFloatOrString::UInt(u) if u <= i64::MAX as u64 => Ok(u as i64),
+        IntegerOrFloatOrString::Float(f)
+            if f.is_finite() && f >= i64::MIN as f64 && f < i64::MAX as f64 =>
+        {
+            Ok(f.trunc() as i64)
+        }
+        IntegerOrFloatOrString::UInt(u) => Err(DeError::custom(format!("integer out of range: {}", u))),
+        IntegerOrFloatOrString::Float(f) => Err(DeError::custom(format!("integer out of range: {}", f))),
+        IntegerOrFloatOrString::String(_) => unreachable!(),
+    }
+}
+
+/// Deserializes `u64`, also accepting numeric strings and truncating floats.
+#[allow(dead_code)]
+pub(crate) fn deserialize_lambda_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match IntegerOrFloatOrString::deserialize(deserializer)?.unquote()? {
+        IntegerOrFloatOrString::Int(i) if i >= 0 => Ok(i as u64),
+        IntegerOrFloatOrString::UInt(u) => Ok(u),
+        IntegerOrFloatOrString::Float(f) if f.is_finite() && f >= 0.0 && f < u64::MAX as f64 => {
+            Ok(f.trunc() as u64)
+        }
+        IntegerOrFloatOrString::Int(i) => Err(DeError::custom(format!("integer out of range: {}", i))),
+        IntegerOrFloatOrString::Float(f) => Err(DeError::custom(format!("integer out of range: {}", f))),
+        IntegerOrFloatOrString::String(_) => unreachable!(),
+    }
+}
+
+/// (De)serializes values Go encodes as bare numbers that may also arrive
+/// quoted, like `big.Int`. Integers are serialized as numbers.
+#[allow(dead_code)]
+pub(crate) mod string_or_number {
+    use serde::de::{Deserialize, Deserializer, Error as DeError};
+    use serde::ser::{Error as SerError, Serialize, Serializer};
+    use serde_json;
+    use serde_json::value::RawValue;
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    /// The raw JSON text of the value, so numbers too large for `u64` keep
+    /// every digit instead of being rounded through `f64`.
+    struct StringOrNumber(Box<RawValue>);
+
+    impl<'de> Deserialize<'de> for StringOrNumber {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Box::<RawValue>::deserialize(deserializer).map(StringOrNumber)
+        }
+    }
+
+    impl StringOrNumber {
+        fn parse<T, E>(self) -> Result<T, E>
+        where
+            T: FromStr,
+            T::Err: Display,
+            E: DeError,
+        {
+            let raw = self.0.get();
+            let text = if raw.starts_with('"') {
+                serde_json::from_str::<String>(raw).map_err(E::custom)?
+            } else if raw.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+                raw.to_string()
+            } else {
+                return Err(E::custom(format!("expected a string or number, found {}", raw)));
+            };
+            text.parse().map_err(E::custom)
+        }
+    }
+
+    fn is_integer(text: &str) -> bool {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+    }
+
+    pub fn deserialize<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr,
+        T::Err: Display,
+    {
+        StringOrNumber::deserialize(deserializer)?.parse()
+    }
+
+    pub fn serialize<S, T>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        T: Display,
+    {
+        let text = value.to_string();
+        if let Ok(i) = text.parse::<i64>() {
+            serializer.serialize_i64(i)
+        } else if let Ok(u) = text.parse::<u64>() {
+            serializer.serialize_u64(u)
+        } else if is_integer(&text) {
+            // Go writes a `big.Int` as bare digits whatever its size.
+            RawValue::from_string(text)
+                .map_err(S::Error::custom)?
+                .serialize(serializer)
+        } else {
+            serializer.serialize_str(&text)
+        }
+    }
+
+    pub mod option {
+        use super::StringOrNumber;
+        use serde::de::{Deserialize, Deserializer};
+        use serde::ser::Serializer;
+        use std::fmt::Display;
+        use std::str::FromStr;
+
+        pub fn deserialize<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: FromStr,
+            T::Err: Display,
+        {
+            match Option::<StringOrNumber>::deserialize(deserializer)? {
+                Some(v) => v.parse().map(Some),
+                None => Ok(None),
+            }
+        }
+
+        pub fn serialize<S, T>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+            T: Display,
+        {
+            match *value {
+                Some(ref v) => super::serialize(v, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
+}
+
+/// (De)serializes a `String` as the array of code points Go encodes a
+/// `[]rune` as. A `null` array is an empty string.
+#[allow(dead_code)]
+pub(crate) mod code_points {
+    use serde::de::{Deserialize, Deserializer, Error as DeError};
+    use serde::ser::Serializer;
+    use std::char;
+
+    fn collect<E: DeError>(points: Vec<u32>) -> Result<String, E> {
+        points
+            .into_iter()
+            .map(|p| char::from_u32(p).ok_or_else(|| E::custom(format!("invalid code point: {}", p))))
+            .collect()
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        collect(Option::<Vec<u32>>::deserialize(deserializer)?.unwrap_or_default())
+    }
+
+    pub fn serialize<S>(value: &str, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(value.chars().map(|c| c as u32))
+    }
+
+    pub mod option {
+        use serde::de::{Deserialize, Deserializer};
+        use serde::ser::Serializer;
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            match Option::<Vec<u32>>::deserialize(deserializer)? {
+                Some(points) => super::collect(points).map(Some),
+                None => Ok(None),
+            }
+        }
+
+        pub fn serialize<S>(value: &Option<String>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match *value {
+                Some(ref v) => super::serialize(v, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -143,6 +403,7 @@ mod test {
     use super::*;
     use chrono::TimeZone;
     use serde_json;
+    use std::collections::{BTreeMap, HashMap};
 
     #[test]
     fn test_deserialize_base64() {
@@ -291,6 +552,72 @@ mod test {
         assert_eq!(Some("foo".to_string()), decoded.v);
     }
 
+    #[test]
+    fn test_string_or_number() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Test {
+            #[serde(with = "string_or_number")]
+            v: String,
+            #[serde(with = "string_or_number::option")]
+            #[serde(default)]
+            o: Option<String>,
+        }
+
+        let decoded: Test = serde_json::from_str(r#"{"v":123,"o":"456"}"#).unwrap();
+        assert_eq!("123".to_string(), decoded.v);
+        assert_eq!(Some("456".to_string()), decoded.o);
+        let decoded: Test = serde_json::from_str(r#"{"v":"-7"}"#).unwrap();
+        assert_eq!("-7".to_string(), decoded.v);
+        assert_eq!(None, decoded.o);
+
+        let instance = Test {
+            v: "123".to_string(),
+            o: Some("123456789012345678901234567890".to_string()),
+        };
+        let encoded = serde_json::to_string(&instance).unwrap();
+        assert_eq!(
+            encoded,
+            r#"{"v":123,"o":123456789012345678901234567890}"#.to_string()
+        );
+
+        let input = r#"{"v":123456789012345678901234567890,"o":-123456789012345678901234567890}"#;
+        let decoded: Test = serde_json::from_str(input).unwrap();
+        assert_eq!("123456789012345678901234567890".to_string(), decoded.v);
+        assert_eq!(
+            Some("-123456789012345678901234567890".to_string()),
+            decoded.o
+        );
+        assert_eq!(input.to_string(), serde_json::to_string(&decoded).unwrap());
+
+        assert!(serde_json::from_str::<Test>(r#"{"v":true}"#).is_err());
+    }
+
+    #[test]
+    fn test_code_points() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Test {
+            #[serde(with = "code_points")]
+            v: String,
+            #[serde(with = "code_points::option")]
+            #[serde(default)]
+            o: Option<String>,
+        }
+
+        let decoded: Test = serde_json::from_str(r#"{"v":[72,105],"o":[128512]}"#).unwrap();
+        assert_eq!("Hi".to_string(), decoded.v);
+        assert_eq!(Some("\u{1F600}".to_string()), decoded.o);
+        assert_eq!(
+            r#"{"v":[72,105],"o":[128512]}"#.to_string(),
+            serde_json::to_string(&decoded).unwrap()
+        );
+
+        let decoded: Test = serde_json::from_str(r#"{"v":null}"#).unwrap();
+        assert_eq!(String::new(), decoded.v);
+        assert_eq!(None, decoded.o);
+
+        assert!(serde_json::from_str::<Test>(r#"{"v":[55296]}"#).is_err());
+    }
+
     #[test]
     fn test_deserialize_map() {
         #[derive(Deserialize)]
@@ -309,5 +636,63 @@ mod test {
         });
         let decoded: Test = serde_json::from_value(input).unwrap();
         assert_eq!(HashMap::new(), decoded.v);
+
+        #[derive(Deserialize)]
+        struct Ordered {
+            #[serde(deserialize_with = "deserialize_lambda_map")]
+            v: BTreeMap<String, String>,
+        }
+        let decoded: Ordered = serde_json::from_value(json!({ "v": null })).unwrap();
+        assert_eq!(BTreeMap::new(), decoded.v);
+    }
+
+    #[test]
+    fn test_deserialize_bool() {
+        #[derive(Deserialize)]
+        struct Test {
+            #[serde(deserialize_with = "deserialize_lambda_bool")]
+            v: bool,
+        }
+        let decoded: Test = serde_json::from_value(json!({ "v": "true" })).unwrap();
+        assert_eq!(true, decoded.v);
+        let decoded: Test = serde_json::from_value(json!({ "v": 0 })).unwrap();
+        assert_eq!(false, decoded.v);
+        let decoded: Test = serde_json::from_value(json!({ "v": true })).unwrap();
+        assert_eq!(true, decoded.v);
+        assert!(serde_json::from_value::<Test>(json!({ "v": "yes" })).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_integers() {
+        #[derive(Deserialize)]
+        struct Test {
+            #[serde(deserialize_with = "deserialize_lambda_i64")]
+            signed: i64,
+            #[serde(deserialize_with = "deserialize_lambda_u64")]
+            unsigned: u64,
+        }
+        let decoded: Test = serde_json::from_value(json!({ "signed": "42", "unsigned": 42 })).unwrap();
+        assert_eq!(42i64, decoded.signed);
+        assert_eq!(42u64, decoded.unsigned);
+        let decoded: Test = serde_json::from_value(json!({ "signed": -1.9, "unsigned": "7.5" })).unwrap();
+        assert_eq!(-1i64, decoded.signed);
+        assert_eq!(7u64, decoded.unsigned);
+        assert!(serde_json::from_value::<Test>(json!({ "signed": 1, "unsigned": -1 })).is_err());
+        assert!(serde_json::from_value::<Test>(json!({ "signed": "x", "unsigned": 1 })).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_boxed_str() {
+        #[derive(Deserialize)]
+        struct Test {
+            #[serde(deserialize_with = "deserialize_lambda_boxed_str")]
+            v: Option<Box<str>>,
+        }
+        let decoded: Test = serde_json::from_value(json!({ "v": "" })).unwrap();
+        assert_eq!(None, decoded.v);
+        let decoded: Test = serde_json::from_value(json!({ "v": null })).unwrap();
+        assert_eq!(None, decoded.v);
+        let decoded: Test = serde_json::from_value(json!({ "v": "foo" })).unwrap();
+        assert_eq!(Some("foo".into()), decoded.v);
     }
 }
@@ -0,0 +1,354 @@
+use codegen;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+/// Identifies a field of a Go struct by their Go names.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldPath {
+    pub struct_name: String,
+    pub field_name: String,
+}
+
+impl FieldPath {
+    pub fn new(struct_name: &str, field_name: &str) -> Self {
+        FieldPath {
+            struct_name: struct_name.to_string(),
+            field_name: field_name.to_string(),
+        }
+    }
+}
+
+/// How the names of Rust fields are derived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldNaming {
+    /// The Go name in `snake_case`.
+    SnakeCase,
+    /// The JSON name, falling back to snake case when it has none or it isn't
+    /// a valid identifier.
+    Original,
+    /// The Go name in `camelCase`.
+    CamelCase,
+}
+
+/// Visibility of the generated items and their fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    /// `pub`
+    Public,
+    /// `pub(crate)`
+    Crate,
+    /// No visibility keyword.
+    Private,
+}
+
+impl Visibility {
+    pub(crate) fn keyword(self) -> Option<&'static str> {
+        match self {
+            Visibility::Public => Some("pub"),
+            Visibility::Crate => Some("pub(crate)"),
+            Visibility::Private => None,
+        }
+    }
+
+    // The keyword followed by a space, to prefix an item with.
+    pub(crate) fn prefix(self) -> &'static str {
+        match self {
+            Visibility::Public => "pub ",
+            Visibility::Crate => "pub(crate) ",
+            Visibility::Private => "",
+        }
+    }
+}
+
+/// A reference counted pointer to wrap a field's type in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedPointer {
+    /// `std::sync::Arc`
+    Arc,
+    /// `std::rc::Rc`
+    Rc,
+}
+
+impl SharedPointer {
+    pub(crate) fn path(self) -> &'static str {
+        match self {
+            SharedPointer::Arc => "std::sync::Arc",
+            SharedPointer::Rc => "std::rc::Rc",
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            SharedPointer::Arc => "Arc",
+            SharedPointer::Rc => "Rc",
+        }
+    }
+}
+
+/// The map type Go maps are translated to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapType {
+    /// `std::collections::HashMap`
+    HashMap,
+    /// `std::collections::BTreeMap`, ordered by key.
+    BTreeMap,
+    /// `indexmap::IndexMap`, in insertion order. Needs its `serde` feature.
+    IndexMap,
+}
+
+impl MapType {
+    pub(crate) fn path(self) -> &'static str {
+        match self {
+            MapType::HashMap => "std::collections::HashMap",
+            MapType::BTreeMap => "std::collections::BTreeMap",
+            MapType::IndexMap => "indexmap::IndexMap",
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            MapType::HashMap => "HashMap",
+            MapType::BTreeMap => "BTreeMap",
+            MapType::IndexMap => "IndexMap",
+        }
+    }
+}
+
+/// A callback run on each generated struct right before it is emitted.
+#[derive(Clone)]
+pub struct StructHook(pub Rc<dyn Fn(&mut codegen::Struct)>);
+
+impl StructHook {
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&mut codegen::Struct) + 'static,
+    {
+        StructHook(Rc::new(hook))
+    }
+}
+
+impl fmt::Debug for StructHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("StructHook")
+    }
+}
+
+/// Options controlling how Go definitions are translated into Rust.
+#[derive(Debug, Clone)]
+pub struct CodegenConfig {
+    /// Route `String` and `HashMap` fields through the `custom_serde`
+    /// helpers of `aws_lambda_events`. When disabled, the generated code only
+    /// relies on `serde` and can be used outside of that crate.
+    pub use_custom_serde: bool,
+    /// Maximum nesting of slices, maps and pointers in a single type. Deeper
+    /// types are rejected with an error instead of recursing without bound.
+    pub max_nesting_depth: usize,
+    /// Emit `pub use` re-exports instead of `pub type` for aliases of Go
+    /// package types that resolve to a single imported Rust type.
+    pub reexport_package_aliases: bool,
+    /// Generate a Rust enum for `string` types that have a block of typed
+    /// constants. Values missing from the block will fail to deserialize.
+    pub string_enums: bool,
+    /// A serde `rename_all` convention (e.g. `"SCREAMING_SNAKE_CASE"`) used for
+    /// generated enums whose values all follow it, instead of renaming every
+    /// variant.
+    pub enum_rename_all: Option<String>,
+    /// Module path that `Base64Data` and the timestamp wrappers are imported
+    /// from, relative to where the generated code is placed.
+    pub encodings_path: String,
+    /// Generate `Cow<'a, str>` instead of `Option<String>` for string fields,
+    /// adding an `'a` lifetime to the structs that (transitively) contain
+    /// them. Optional and nested strings stay owned.
+    pub borrowed_strings: bool,
+    /// Map `json.RawMessage` to `Box<serde_json::value::RawValue>`, keeping
+    /// the original JSON text. Needs serde_json's `raw_value` feature, and
+    /// structs (transitively) holding one don't derive `PartialEq`.
+    pub raw_json_values: bool,
+    /// Extra mappings for Go package types like `mylib.UUID`, to a Rust type
+    /// and the imports it needs. These take precedence over the built-in ones.
+    pub package_type_mappings: HashMap<String, (String, HashSet<String>)>,
+    /// Import paths keyed by the name a package is referenced by, used to
+    /// resolve package types. The source's own `import`s take precedence.
+    pub import_paths: HashMap<String, String>,
+    /// Map `big.Int` to `num_bigint::BigInt` and `big.Float` to
+    /// `bigdecimal::BigDecimal` (which needs its `serde` feature). Otherwise
+    /// both are kept as a `String`.
+    pub big_numbers: bool,
+    /// Candidate Rust types for `interface{}` fields known to hold one of a
+    /// few shapes. Such fields get a generated `#[serde(untagged)]` enum,
+    /// with variants tried in order, instead of a generic `Value`.
+    pub untagged_interfaces: HashMap<FieldPath, Vec<String>>,
+    /// Add a flattened `extra` map to each struct that keeps any fields not
+    /// otherwise captured.
+    pub capture_extra: bool,
+    /// Skip serializing `Option` fields that are `None`, like Go does for
+    /// `omitempty` and `nil` pointers, and empty `omitempty` maps.
+    pub skip_serializing_none: bool,
+    /// Map `interface{}` and `json.RawMessage` to `serde_json::Value` instead
+    /// of adding a generic parameter for each of them.
+    pub interfaces_as_value: bool,
+    /// Accept `"true"`/`"false"` and `1`/`0` for `bool` fields. Needs
+    /// `use_custom_serde`; optional fields are left as-is.
+    pub tolerant_bool: bool,
+    /// Map `[]rune` to `String` instead of `Vec<i32>`, (de)serialized from
+    /// code points with `custom_serde`'s `code_points`. Runes are `i32`
+    /// rather than `char` by default: Go writes them as numbers, which
+    /// serde's `char` (a one-character string) can't read.
+    pub rune_slice_as_string: bool,
+    /// Give string enums an `ALL` constant listing every variant.
+    pub emit_enum_all: bool,
+    /// Map floats to `ordered_float::OrderedFloat` (which needs its `serde`
+    /// feature), and derive `Eq` and `Hash` for structs whose fields all
+    /// support it.
+    pub ordered_floats: bool,
+    /// Naming of the generated fields. Renames are only added where the name
+    /// differs from the JSON one.
+    pub field_naming: FieldNaming,
+    /// Called with every generated struct, to add attributes or fields that
+    /// have no dedicated option.
+    pub struct_hook: Option<StructHook>,
+    /// Container-level serde renames of generated structs, keyed by their Go
+    /// name. These change how a struct appears as a tagged enum's payload.
+    pub struct_renames: HashMap<String, String>,
+    /// Doc comments replacing those from the Go source, keyed by the Go
+    /// name of the struct.
+    pub doc_overrides: HashMap<String, String>,
+    /// Use the names of `bson` tags when the generated crate's `bson` feature
+    /// is enabled. Fields whose JSON name differs then only get their JSON
+    /// rename without that feature.
+    pub bson_renames: bool,
+    /// Accept numeric strings and truncate floats for integer fields. Needs
+    /// `use_custom_serde`; optional fields are left as-is.
+    pub tolerant_numbers: bool,
+    /// Visibility of the generated structs, fields, aliases and enums.
+    pub visibility: Visibility,
+    /// Generate `const`s for the integer constants of `const (...)` blocks.
+    /// Values may reference constants defined earlier in the same block.
+    pub emit_constants: bool,
+    /// Rust module paths keyed by Go package name, imported for aliases like
+    /// `type Foo = events.Bar` of types without a known mapping. Such aliases
+    /// refer to the struct generated for the type either way.
+    pub alias_import_paths: HashMap<String, String>,
+    /// Give structs with `Base64Data` fields a `<field>_bytes()` accessor
+    /// for each of them.
+    pub base64_accessors: bool,
+    /// Derive `Eq`, `PartialOrd` and `Ord` for structs whose fields all
+    /// support it. Floats only do with `ordered_floats`.
+    pub derive_ord: bool,
+    /// Map `database/sql` wrappers like `sql.NullString` to a plain
+    /// `Option`, for consumers that flatten them to the bare value or `null`.
+    /// They are (de)serialized in Go's `{"String": ..., "Valid": ...}` shape
+    /// otherwise.
+    pub sql_nulls_as_option: bool,
+    /// Fields whose type is wrapped in an `Arc` or `Rc`, inside the `Option`
+    /// of optional ones. Needs serde's `rc` feature in the generated crate.
+    pub shared_fields: HashMap<FieldPath, SharedPointer>,
+    /// Make `time.Time` fields with `omitempty` optional. Go never omits
+    /// them, so they can stay required. Pointers are optional either way.
+    pub time_omitempty_as_option: bool,
+    /// Start each generated file with `#![allow(unused_imports)]`, for
+    /// modules that don't use every import they get. The file is then a
+    /// single raw item, so it can't be passed to `merge`.
+    pub allow_unused_imports: bool,
+    /// Like `allow_unused_imports`, for `dead_code`.
+    pub allow_dead_code: bool,
+    /// (De)serialize numbers and booleans with the `,string` tag option
+    /// through `serde_with::DisplayFromStr`, in place of any `custom_serde`
+    /// helper.
+    pub use_serde_with: bool,
+    /// Give string enums and dispatch enums a `kind()` method returning the
+    /// serialized value or tag of the active variant.
+    pub emit_enum_kind: bool,
+    /// Map `uuid.UUID` from `github.com/google/uuid` to `uuid::Uuid` (which
+    /// needs its `serde` feature). Otherwise it is kept as a `String`.
+    pub uuids: bool,
+    /// Use `Box<str>` for `string` fields, saving a word each. Slices and
+    /// maps of strings keep `String`.
+    pub strings_as_boxed_str: bool,
+    /// Implement `FromStr` and `TryFrom<&str>` for string enums, accepting
+    /// the serialized value of each variant.
+    pub impl_from_str: bool,
+    /// Map type used for Go maps. Generated helpers like the `extra` field of
+    /// `capture_extra` stay a `HashMap`.
+    pub map_type: MapType,
+    /// Emit a private module implementing each `// layout: ...` time format
+    /// instead of expecting one in scope.
+    pub emit_layout_modules: bool,
+    /// Map package types without a known translation, like `somepkg.Thing`,
+    /// to `serde_json::Value` instead of failing.
+    pub unknown_package_as_value: bool,
+    /// Emit a `#[serde(remote = "...")]` copy of `SecondTimestamp` and
+    /// `MillisecondTimestamp` for their fields to use, so their format can
+    /// be changed in the generated code.
+    pub remote_timestamp_shims: bool,
+    /// Size of Go's platform sized `int` and `uint`, 32 or 64. Sized types
+    /// like `int32` always map to 64 bits.
+    pub platform_int_bits: u32,
+    /// Generate a `validate` method checking the `required`, `min` and `max`
+    /// constraints of `validate` tags. Others are ignored.
+    pub generate_validate: bool,
+    /// Treat `omitempty` fields whose type is an alias of a slice, like
+    /// `type IDs []string`, as slices: default to empty and skip them when
+    /// empty instead of wrapping them in an `Option`.
+    pub omitempty_slice_aliases: bool,
+    /// Emit defined types over primitives, like `type UserID string`, as
+    /// `#[serde(transparent)]` newtypes instead of `pub type` aliases.
+    /// Aliases written `type X = Y` are unaffected.
+    pub defined_type_newtypes: bool,
+}
+
+impl Default for CodegenConfig {
+    fn default() -> Self {
+        CodegenConfig {
+            use_custom_serde: true,
+            max_nesting_depth: 64,
+            reexport_package_aliases: false,
+            string_enums: false,
+            enum_rename_all: None,
+            encodings_path: "super::super::encodings".to_string(),
+            borrowed_strings: false,
+            raw_json_values: false,
+            package_type_mappings: HashMap::new(),
+            import_paths: HashMap::new(),
+            big_numbers: false,
+            untagged_interfaces: HashMap::new(),
+            capture_extra: false,
+            skip_serializing_none: false,
+            interfaces_as_value: false,
+            tolerant_bool: false,
+            rune_slice_as_string: false,
+            emit_enum_all: false,
+            ordered_floats: false,
+            field_naming: FieldNaming::SnakeCase,
+            struct_hook: None,
+            struct_renames: HashMap::new(),
+            doc_overrides: HashMap::new(),
+            bson_renames: false,
+            tolerant_numbers: false,
+            visibility: Visibility::Public,
+            emit_constants: false,
+            alias_import_paths: HashMap::new(),
+            base64_accessors: false,
+            derive_ord: false,
+            sql_nulls_as_option: false,
+            shared_fields: HashMap::new(),
+            time_omitempty_as_option: true,
+            allow_unused_imports: false,
+            allow_dead_code: false,
+            use_serde_with: false,
+            emit_enum_kind: false,
+            uuids: false,
+            strings_as_boxed_str: false,
+            impl_from_str: false,
+            map_type: MapType::HashMap,
+            emit_layout_modules: false,
+            unknown_package_as_value: false,
+            remote_timestamp_shims: false,
+            platform_int_bits: 64,
+            generate_validate: false,
+            omitempty_slice_aliases: false,
+            defined_type_newtypes: false,
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Errors returned when translating Go code into Rust.
+#[derive(Debug)]
+pub enum CodegenError {
+    /// The Go source doesn't match the grammar.
+    Parse(String),
+    /// The Go source uses a type with no Rust mapping.
+    UnsupportedType(String),
+    /// Reading the Go source or writing the generated code failed.
+    Io(io::Error),
+    /// The Go source is valid but exceeds a limit or conflicts with the
+    /// configuration.
+    Validation(String),
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CodegenError::Parse(ref e) => write!(f, "failed to parse Go source: {}", e),
+            CodegenError::UnsupportedType(ref t) => write!(f, "unsupported Go type: {}", t),
+            CodegenError::Io(ref e) => write!(f, "I/O error: {}", e),
+            CodegenError::Validation(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for CodegenError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            CodegenError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CodegenError {
+    fn from(e: io::Error) -> Self {
+        CodegenError::Io(e)
+    }
+}
@@ -8,24 +8,30 @@ extern crate pest;
 #[macro_use]
 extern crate pest_derive;
 extern crate codegen;
-extern crate failure;
 extern crate heck;
 extern crate regex;
 #[macro_use]
 extern crate lazy_static;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
+mod config;
+mod error;
+
+pub use config::{CodegenConfig, FieldNaming, FieldPath, MapType, SharedPointer, StructHook, Visibility};
+pub use error::CodegenError;
 use codegen::{Field, Scope, Struct};
-use failure::Error;
-use heck::{CamelCase, SnakeCase};
+use heck::{CamelCase, MixedCase, ShoutySnakeCase, SnakeCase};
 use pest::iterators::Pairs;
 use pest::Parser;
 use regex::Regex;
 use std::boxed::Box;
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[grammar = "aws_go_events.pest"]
@@ -38,11 +44,15 @@ impl fmt::Display for GoCode {
         write!(f, "{}", self.0)
     }
 }
+// The libraries the scope imports are kept since they can't be read back
+// once the imports are rendered into a raw item.
 #[derive(Debug, Clone)]
-pub struct RustCode(Scope);
+pub struct RustCode(Scope, HashSet<String>);
 impl RustCode {
     pub fn new(text: String) -> Self {
-        RustCode(Scope::new().raw(&text).clone())
+        let scope = Scope::new().raw(&text).clone();
+        let libraries = imported_libraries(&scope);
+        RustCode(scope, libraries)
     }
     pub fn scope(&self) -> codegen::Scope {
         self.0.clone()
@@ -63,7 +73,21 @@ impl PartialEq for RustCode {
     }
 }
 
-pub fn parse_go_file(path: &PathBuf) -> Result<(GoCode, RustCode), Error> {
+/// The sorted import paths the code needs, like `std::collections::HashMap`.
+pub fn required_imports(code: &RustCode) -> Vec<String> {
+    let mut imports: Vec<String> = code.1.iter().cloned().collect();
+    imports.sort();
+    imports
+}
+
+pub fn parse_go_file(path: &PathBuf) -> Result<(GoCode, RustCode), CodegenError> {
+    parse_go_file_with_config(path, &CodegenConfig::default())
+}
+
+pub fn parse_go_file_with_config(
+    path: &PathBuf,
+    config: &CodegenConfig,
+) -> Result<(GoCode, RustCode), CodegenError> {
     debug!("Parsing path: {:?}", &path.display());
 
     // Read the go code.
@@ -73,7 +97,61 @@ pub fn parse_go_file(path: &PathBuf) -> Result<(GoCode, RustCode), Error> {
     debug!("\n{}\n", go_code);
 
     // parse the go code into rust code.
-    Ok(parse_go_string(go_code)?)
+    parse_go_string_with_config(go_code, config)
+}
+
+/// Translates a Go file and writes the result to `dest`. A destination that
+/// already has the same content is left untouched, keeping its mtime so
+/// build scripts don't trigger needless recompiles. Returns whether the file
+/// was written.
+pub fn generate_to_path(
+    path: &PathBuf,
+    dest: &Path,
+    config: &CodegenConfig,
+) -> Result<bool, CodegenError> {
+    let (_, rust_code) = parse_go_file_with_config(path, config)?;
+    let rendered = rust_code.to_string();
+
+    if let Ok(existing) = fs::read_to_string(dest) {
+        if existing == rendered {
+            debug!("Skipping unchanged {:?}", dest.display());
+            return Ok(false);
+        }
+    }
+
+    // Write next to the destination and rename it into place so readers
+    // never see a partial file.
+    let mut tmp = dest.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut f = File::create(&tmp)?;
+        f.write_all(rendered.as_bytes())?;
+    }
+    fs::rename(&tmp, dest)?;
+    Ok(true)
+}
+
+/// Returns the commands of the `//go:generate` directives in a Go file, in
+/// order.
+pub fn collect_go_generate_directives(path: &PathBuf) -> Result<Vec<String>, CodegenError> {
+    let mut f = File::open(path)?;
+    let mut go_code = String::new();
+    f.read_to_string(&mut go_code)?;
+
+    let mut commands = vec![];
+    for line in go_code.lines() {
+        // Lines that aren't directives don't parse.
+        if let Ok(pairs) = AwsGoEventsParser::parse(Rule::go_generate, line.trim()) {
+            for pair in pairs.flatten() {
+                if pair.as_rule() == Rule::go_generate_command {
+                    commands.push(pair.into_span().as_str().trim().to_string());
+                }
+            }
+        }
+    }
+
+    Ok(commands)
 }
 
 fn add_sorted_imports(scope: &mut Scope, libraries: &HashSet<String>) {
@@ -89,40 +167,367 @@ fn add_sorted_imports(scope: &mut Scope, libraries: &HashSet<String>) {
     }
 }
 
-pub fn parse_go_string(go_source: String) -> Result<(GoCode, RustCode), Error> {
+/// Combines generated code into a single scope, importing each library once.
+/// Helper items several inputs need, like `Empty`, are only kept once.
+pub fn merge(codes: Vec<RustCode>) -> RustCode {
+    let mut libraries = HashSet::new();
+    let mut items = vec![];
+    for code in codes {
+        libraries.extend(code.1);
+        items.extend(code.0.items().iter().cloned());
+    }
+
+    let mut scope = Scope::new();
+    add_sorted_imports(&mut scope, &libraries);
+    let mut emitted = HashMap::new();
+    for item in items {
+        push_unique_item(&mut scope, item, &mut emitted);
+    }
+    RustCode(scope, libraries)
+}
+
+/// Translates Go source into an existing scope, after the items already in
+/// it. Imports are added to the scope's own, and helpers the scope already
+/// defines aren't repeated. `allow_unused_imports` and
+/// `allow_dead_code` are ignored, inner attributes are up to the scope's
+/// owner.
+pub fn emit_into_scope(go_source: &str, scope: &mut Scope, config: &CodegenConfig) -> Result<(), CodegenError> {
+    let config = CodegenConfig {
+        allow_unused_imports: false,
+        allow_dead_code: false,
+        ..config.clone()
+    };
+    let (_, code) = parse_go_string_with_config(go_source.to_string(), &config)?;
+    add_sorted_imports(scope, &code.1);
+    let mut emitted = HashMap::new();
+    for item in scope.items() {
+        if let codegen::Item::Raw(ref r) = *item {
+            if let Some(name) = raw_item_name(r) {
+                emitted.insert(name, r.clone());
+            }
+        }
+    }
+    for item in code.0.items() {
+        push_unique_item(scope, item.clone(), &mut emitted);
+    }
+    Ok(())
+}
+
+// Generated helpers are raw items, skipped when one of the same name is
+// already in `emitted`.
+fn push_unique_item(scope: &mut Scope, item: codegen::Item, emitted: &mut HashMap<String, String>) {
+    if let codegen::Item::Raw(ref r) = item {
+        if let Some(name) = raw_item_name(r) {
+            match emitted.get(&name) {
+                Some(previous) if previous != r => {
+                    warn!("Skipping a second, different definition of {}", name);
+                    return;
+                }
+                Some(_) => return,
+                None => {
+                    emitted.insert(name, r.clone());
+                }
+            }
+        }
+    }
+    push_item(scope, item);
+}
+
+// The name a raw item defines, from its first line that isn't an attribute
+// or comment.
+fn raw_item_name(raw: &str) -> Option<String> {
+    let line = raw
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with("#[") && !l.starts_with("//"))?;
+    let mut words = line.split_whitespace().skip_while(|w| w.starts_with("pub"));
+    match words.next()? {
+        "struct" | "enum" | "mod" | "const" | "type" => {}
+        _ => return None,
+    }
+    let name: String = words
+        .next()?
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn push_item(scope: &mut Scope, item: codegen::Item) {
+    match item {
+        codegen::Item::Module(m) => scope.push_module(m),
+        codegen::Item::Function(f) => scope.push_fn(f),
+        codegen::Item::Struct(s) => scope.push_struct(s),
+        codegen::Item::Trait(t) => scope.push_trait(t),
+        codegen::Item::Enum(e) => scope.push_enum(e),
+        codegen::Item::Impl(i) => scope.push_impl(i),
+        codegen::Item::Raw(r) => scope.raw(&r),
+    };
+}
+
+// `codegen` doesn't expose a scope's imports, so read them back from the
+// `use` lines it renders first.
+fn imported_libraries(scope: &Scope) -> HashSet<String> {
+    let mut libraries = HashSet::new();
+    for line in scope.to_string().lines() {
+        let import = match line.strip_prefix("use ").and_then(|l| l.strip_suffix(';')) {
+            Some(import) => import,
+            None => break,
+        };
+        match import.find("::{") {
+            Some(i) => {
+                let path = &import[..i];
+                for ty in import[i + 3..].trim_end_matches('}').split(", ") {
+                    libraries.insert(format!("{}::{}", path, ty));
+                }
+            }
+            None => {
+                libraries.insert(import.to_string());
+            }
+        }
+    }
+    libraries
+}
+
+/// Builds an enum over generated structs, internally tagged with `tag`, from
+/// `(variant, struct)` pairs. It is returned as code rather than a
+/// `codegen::Enum` since those can't carry the `serde` attribute.
+pub fn generate_dispatch_enum(name: &str, variants: &[(String, String)], tag: &str) -> RustCode {
+    generate_dispatch_enum_with_config(name, variants, tag, &CodegenConfig::default())
+}
+
+/// Like `generate_dispatch_enum`, along with the impls `config` asks for.
+pub fn generate_dispatch_enum_with_config(
+    name: &str,
+    variants: &[(String, String)],
+    tag: &str,
+    config: &CodegenConfig,
+) -> RustCode {
+    // XXX: Add enum annotation support to `codegen`
+    let mut out = String::new();
+    out.push_str("#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]\n");
+    out.push_str(&format!("#[serde(tag = {:?})]\n", tag));
+    out.push_str(&format!("pub enum {} {{\n", name));
+    for (variant, struct_name) in variants {
+        out.push_str(&format!("    {}({}),\n", variant, struct_name));
+    }
+    out.push('}');
+
+    let mut scope = Scope::new();
+    scope.raw(&out);
+    if config.emit_enum_kind {
+        // Serde tags the variants with their names.
+        let arms: Vec<(String, String)> = variants
+            .iter()
+            .map(|(variant, _)| (format!("{}::{}(_)", name, variant), variant.clone()))
+            .collect();
+        scope.push_impl(enum_kind_impl(name, &arms, config.visibility));
+    }
+    RustCode(scope, HashSet::new())
+}
+
+// `FromStr` and `TryFrom<&str>` for a string enum, from `(variant, serialized)`
+// pairs. Paths are absolute so no imports are needed.
+fn from_str_impls(name: &str, arms: &[(String, String)]) -> Vec<codegen::Impl> {
+    let mut from_str = codegen::Impl::new(name);
+    from_str.impl_trait("::std::str::FromStr").associate_type("Err", "String");
+    let function = from_str.new_fn("from_str");
+    function
+        .arg("s", "&str")
+        .ret("Result<Self, Self::Err>")
+        .line("match s {");
+    for (variant, serialized) in arms {
+        function.line(format!("    {:?} => Ok({}),", serialized, variant));
+    }
+    function
+        .line(format!("    _ => Err(format!(\"unknown {}: {{}}\", s)),", name))
+        .line("}");
+
+    let mut try_from = codegen::Impl::new(name);
+    try_from
+        .generic("'a")
+        .impl_trait("::std::convert::TryFrom<&'a str>")
+        .associate_type("Error", "String");
+    try_from
+        .new_fn("try_from")
+        .arg("s", "&'a str")
+        .ret("Result<Self, Self::Error>")
+        .line("s.parse()");
+
+    vec![from_str, try_from]
+}
+
+// A `kind()` method for an enum, from `(pattern, serialized)` pairs.
+fn enum_kind_impl(name: &str, arms: &[(String, String)], vis: Visibility) -> codegen::Impl {
+    let mut imp = codegen::Impl::new(name);
+    let function = imp.new_fn("kind");
+    if let Some(vis) = vis.keyword() {
+        function.vis(vis);
+    }
+    function.arg_ref_self().ret("&'static str").line("match *self {");
+    for (pattern, serialized) in arms {
+        function.line(format!("    {} => \"{}\",", pattern, serialized));
+    }
+    function.line("}");
+    imp
+}
+
+pub fn parse_go_string(go_source: String) -> Result<(GoCode, RustCode), CodegenError> {
+    parse_go_string_with_config(go_source, &CodegenConfig::default())
+}
+
+pub fn parse_go_string_with_config(
+    go_source: String,
+    config: &CodegenConfig,
+) -> Result<(GoCode, RustCode), CodegenError> {
+    // Some editors start files with a byte-order mark.
+    let go_source = match go_source.strip_prefix('\u{feff}') {
+        Some(stripped) => stripped.to_string(),
+        None => go_source,
+    };
     let source = go_source.clone();
 
+    check_source_nesting(&source, config)?;
     let pairs = AwsGoEventsParser::parse(Rule::aws_go_events, &source.trim())
-        .unwrap_or_else(|e| panic!("{}", e));
+        .map_err(|e| CodegenError::Parse(e.to_string()))?;
+
+    // Package types are resolved through the file's imports.
+    let imports = parse_imports(pairs.clone());
+    let resolved;
+    let config = if imports.is_empty() {
+        config
+    } else {
+        let mut c = config.clone();
+        c.import_paths.extend(imports);
+        resolved = c;
+        &resolved
+    };
 
     let mut scope = Scope::new();
 
+    // Typed string constants may be declared after the type they belong to.
+    let mut enum_values: HashMap<String, Vec<EnumValue>> = HashMap::new();
+    if config.string_enums {
+        for pair in pairs.clone() {
+            if pair.as_rule() == Rule::enum_options {
+                parse_enum_options(pair.into_span().as_str(), &mut enum_values);
+            }
+        }
+    }
+
+    let mut context = parse_struct_context(pairs.clone(), config)?;
+    for (type_name, values) in &enum_values {
+        for value in values {
+            context.constants.insert(
+                value.name.clone(),
+                format!("{}::{}", type_name.to_camel_case(), enum_variant_name(type_name, &value.name)),
+            );
+        }
+    }
+
+    // Serialized keys of each struct, used to spot clashing flattened fields.
+    let mut struct_keys: HashMap<String, HashSet<String>> = HashMap::new();
+    let mut flattened: Vec<(String, Vec<String>)> = Vec::new();
+    // Go name and source of each generated struct, keyed by its Rust name.
+    let mut defined: HashMap<String, (String, String)> = HashMap::new();
+    // Items shared by several structs are only emitted once.
+    let mut emitted: HashSet<String> = HashSet::new();
+
     for pair in pairs {
         match pair.as_rule() {
             Rule::struct_def => {
-                let (parsed_struct, required_libraries) = parse_struct(pair.into_inner())?;
-                scope.push_struct(parsed_struct);
-                add_sorted_imports(&mut scope, &required_libraries);
+                let source = pair.clone().into_span().as_str().trim().to_string();
+                let parsed = parse_struct(pair.into_inner(), config, &context)?;
+                if let Some((go_name, existing)) = defined.get(&parsed.name) {
+                    if *existing == source {
+                        debug!("Skipping identical definition of {}", go_name);
+                        continue;
+                    }
+                    if *go_name == parsed.go_name {
+                        return Err(CodegenError::Validation(format!(
+                            "struct `{}` is defined more than once with different contents",
+                            go_name
+                        )));
+                    }
+                    return Err(CodegenError::Validation(format!(
+                        "structs `{}` and `{}` both generate `{}`",
+                        go_name, parsed.go_name, parsed.name
+                    )));
+                }
+                defined.insert(parsed.name.clone(), (parsed.go_name.clone(), source));
+                if parsed.embedded.len() > 1 {
+                    flattened.push((parsed.name.clone(), parsed.embedded.clone()));
+                }
+                struct_keys.insert(parsed.name.clone(), parsed.keys.clone());
+                push_parsed_struct(&mut scope, parsed, &mut emitted);
             }
             Rule::type_alias => {
-                let alias = parse_type_alias(pair.into_inner())?;
-                if let Some((name, target)) = alias {
-                    add_sorted_imports(&mut scope, &target.libraries);
+                let alias = parse_type_alias(pair.into_inner(), config)?;
+                if let Some(alias) = alias {
+                    if !alias.from_package && alias.target.value == "String" {
+                        if let Some(values) = enum_values.get(&alias.name) {
+                            scope.raw(&render_string_enum(&alias.name, values, config)?);
+                            continue;
+                        }
+                    }
+                    if let Some(path) = reexported_path(&alias, config) {
+                        scope.raw(&format!("{}use {} as {};", config.visibility.prefix(), path, alias.name));
+                        continue;
+                    }
+                    add_sorted_imports(&mut scope, &alias.target.libraries);
+                    if alias.newtype {
+                        // Named like the fields referring to it.
+                        scope.raw(&render_newtype(
+                            &alias.name.to_camel_case(),
+                            &alias.target.value,
+                            config.visibility,
+                        ));
+                        continue;
+                    }
                     // XXX: Add type definition support to `codegen`
-                    for a in target.annotations {
+                    for a in alias.target.annotations {
                         scope.raw(&format!("#[{}]", a));
                     }
-                    scope.raw(&format!("pub type {} = {};", name, target.value));
+                    let params = if alias.params.is_empty() {
+                        String::new()
+                    } else {
+                        format!("<{}>", alias.params.join(", "))
+                    };
+                    scope.raw(&format!(
+                        "{}type {}{} = {};",
+                        config.visibility.prefix(),
+                        alias.name,
+                        params,
+                        alias.target.value
+                    ));
+                }
+            }
+            Rule::any_comment => {
+                let comment = pair.clone().into_span().as_str();
+                match parse_banner(comment) {
+                    Some(title) => {
+                        scope.raw(&title);
+                    }
+                    None => debug!("Skipping: {}", comment),
+                }
+            }
+            Rule::enum_options if config.emit_constants => {
+                for constant in fold_constants(pair.into_span().as_str()) {
+                    scope.raw(&render_constant(&constant, config.visibility));
                 }
             }
             // Skip some things for now.
-            Rule::any_comment
-            | Rule::constant_def
+            Rule::constant_def
             | Rule::package_def
             | Rule::import
             | Rule::import_multiple
             | Rule::function
-            | Rule::enum_options => {
+            | Rule::enum_options
+            | Rule::constraint_interface => {
                 debug!("Skipping: {}", pair.clone().into_span().as_str());
                 ()
             }
@@ -136,6 +541,12 @@ pub fn parse_go_string(go_source: String) -> Result<(GoCode, RustCode), Error> {
         }
     }
 
+    for (name, embedded) in flattened {
+        for key in overlapping_keys(&embedded, &struct_keys) {
+            warn!("Flattened fields of `{}` both contain the key `{}`", name, key);
+        }
+    }
+
     debug!("{}", &scope.to_string());
 
     /*
@@ -144,1119 +555,6776 @@ pub fn parse_go_string(go_source: String) -> Result<(GoCode, RustCode), Error> {
             .expect("formatted code");
     */
 
-    Ok((GoCode(go_source), RustCode(scope)))
+    let libraries = imported_libraries(&scope);
+
+    let mut allowed = vec![];
+    if config.allow_unused_imports {
+        allowed.push("unused_imports");
+    }
+    if config.allow_dead_code {
+        allowed.push("dead_code");
+    }
+    if !allowed.is_empty() {
+        // XXX: `codegen` always renders imports first, but inner attributes
+        // must precede them.
+        let rendered = scope.to_string();
+        scope = Scope::new();
+        scope.raw(&format!("#![allow({})]\n\n{}", allowed.join(", "), rendered));
+    }
+
+    Ok((GoCode(go_source), RustCode(scope, libraries)))
 }
 
-#[derive(Debug, Clone)]
-struct FieldDef {
-    name: String,
-    json_name: Option<String>,
-    comments: Vec<String>,
-    omit_empty: bool,
-    go_type: GoType,
-    embedded: bool,
+/// Translates the source of exactly one Go struct into the Rust struct and
+/// the imports and helpers it needs.
+pub fn render_struct(go_struct_source: &str, config: &CodegenConfig) -> Result<String, CodegenError> {
+    let source = go_struct_source.trim();
+    check_source_nesting(source, config)?;
+    let pairs = AwsGoEventsParser::parse(Rule::struct_def, source)
+        .map_err(|e| CodegenError::Parse(e.to_string()))?;
+    let pair = pairs.clone().next().expect("parsed struct");
+    if pair.clone().into_span().end() != source.len() {
+        return Err(CodegenError::Parse(
+            "expected a single struct definition".to_string(),
+        ));
+    }
+
+    let context = parse_struct_context(pairs, config)?;
+    let parsed = parse_struct(pair.into_inner(), config, &context)?;
+    let mut scope = Scope::new();
+    push_parsed_struct(&mut scope, parsed, &mut HashSet::new());
+    Ok(scope.to_string())
 }
 
-fn parse_comment(c: &str) -> String {
-    c.replacen("//", "", 1).trim().to_string()
+// Items already in `emitted` are skipped.
+fn push_parsed_struct(scope: &mut Scope, parsed: ParsedStruct, emitted: &mut HashSet<String>) {
+    if parsed.attributes.is_empty() && parsed.pre_derive_attributes.is_empty() {
+        scope.push_struct(parsed.rust_struct);
+    } else {
+        scope.raw(&render_struct_with_attributes(
+            parsed.rust_struct,
+            &parsed.pre_derive_attributes,
+            &parsed.attributes,
+        ));
+    }
+    for item in parsed.raw_items {
+        if emitted.insert(item.clone()) {
+            scope.raw(&item);
+        }
+    }
+    for function in parsed.functions {
+        scope.push_fn(function);
+    }
+    for imp in parsed.impls {
+        scope.push_impl(imp);
+    }
+    add_sorted_imports(scope, &parsed.libraries);
+    for nested in parsed.nested {
+        push_parsed_struct(scope, nested, emitted);
+    }
 }
 
-fn parse_type_alias(pairs: Pairs<Rule>) -> Result<Option<(String, RustType)>, Error> {
-    debug!("Parsing type alias");
-    let mut value = None;
-    for pair in pairs {
-        match pair.as_rule() {
-            Rule::local_type_alias => {
-                value = parse_local_type_alias(pair.into_inner())?;
+// XXX: Add container attribute support to `codegen`
+fn render_struct_with_attributes(
+    rust_struct: codegen::Struct,
+    pre_derive_attributes: &[String],
+    attributes: &[String],
+) -> String {
+    let mut struct_scope = Scope::new();
+    struct_scope.push_struct(rust_struct);
+    let mut out = String::new();
+    let mut inserted = false;
+    for line in struct_scope.to_string().lines() {
+        if line.starts_with("#[derive(") {
+            for a in pre_derive_attributes {
+                out.push_str(a);
+                out.push('\n');
             }
-            Rule::package_type_alias => {
-                value = parse_package_type_alias(pair.into_inner())?;
+        }
+        if !inserted && !line.starts_with("///") && !line.starts_with("#[") {
+            for a in attributes {
+                out.push_str(a);
+                out.push('\n');
             }
-            _ => unreachable!(),
+            inserted = true;
         }
+        out.push_str(line);
+        out.push('\n');
     }
-    Ok(value)
+    out.trim_end().to_string()
 }
 
-fn parse_local_type_alias(pairs: Pairs<Rule>) -> Result<Option<(String, RustType)>, Error> {
-    debug!("Parsing local type alias");
-    let mut name: Option<String> = None;
-    let mut target: Option<GoType> = None;
+struct TypeAlias {
+    name: String,
+    target: RustType,
+    // Whether the target is a type from another Go package.
+    from_package: bool,
+    // Whether this is a defined type over a primitive, emitted as a newtype.
+    newtype: bool,
+    // Names of the type parameters, like `T` in `type List[T any] []T`.
+    params: Vec<String>,
+}
 
-    for pair in pairs {
-        let span = pair.clone().into_span();
-        match pair.as_rule() {
-            Rule::ident => name = Some(mangle(span.as_str())),
-            Rule::type_alias_target => {
-                target = Some(parse_go_type(pair.into_inner())?);
-            }
-            _ => unreachable!(),
-        }
+// Package aliases resolving to a single imported Rust type can be re-exported
+// as-is instead of being declared as a new alias.
+fn reexported_path(alias: &TypeAlias, config: &CodegenConfig) -> Option<String> {
+    if !config.reexport_package_aliases
+        || !alias.from_package
+        || !alias.target.annotations.is_empty()
+    {
+        return None;
     }
+    alias
+        .target
+        .libraries
+        .iter()
+        .find(|lib| lib.rsplit("::").next() == Some(alias.target.value.as_str()))
+        .cloned()
+}
 
-    let name = name.expect("parsed name");
-    let target = target.expect("parsed target");
-
-    Ok(Some((name, translate_go_type_to_rust_type(target, None)?)))
+#[derive(Debug, Clone)]
+struct EnumValue {
+    name: String,
+    value: String,
+    comments: Vec<String>,
 }
 
-fn parse_package_type_alias(pairs: Pairs<Rule>) -> Result<Option<(String, RustType)>, Error> {
-    debug!("Parsing package type alias");
-    let mut name: Option<String> = None;
-    let mut target: Option<GoType> = None;
+// Collects the typed string constants of a `const (...)` block, keyed by
+// their Go type.
+fn parse_enum_options(text: &str, enums: &mut HashMap<String, Vec<EnumValue>>) {
+    lazy_static! {
+        static ref CONST_RE: Regex =
+            Regex::new(r#"^(\w+)\s+(\w+)\s*=\s*"([^"]*)"\s*(//.*)?$"#).expect("regex to compile");
+    }
 
-    for pair in pairs {
-        let span = pair.clone().into_span();
-        let value = span.as_str();
-        match pair.as_rule() {
-            Rule::ident => name = Some(mangle(span.as_str())),
-            Rule::package_ident => {
-                target = Some(parse_go_package_ident(value)?);
+    let body = match (text.find('('), text.rfind(')')) {
+        (Some(start), Some(end)) if start < end => &text[start + 1..end],
+        _ => return,
+    };
+
+    let mut comments = vec![];
+    for line in body.lines() {
+        let line = line.trim();
+        if line.starts_with("//") {
+            comments.push(parse_comment(line));
+        } else if let Some(caps) = CONST_RE.captures(line) {
+            let mut value = EnumValue {
+                name: caps[1].to_string(),
+                value: caps[3].to_string(),
+                comments: std::mem::take(&mut comments),
+            };
+            if let Some(inline_comment) = caps.get(4) {
+                value.comments.push(parse_comment(inline_comment.as_str()));
             }
-            _ => unreachable!(),
+            enums
+                .entry(caps[2].to_string())
+                .or_default()
+                .push(value);
+        } else {
+            comments.clear();
         }
     }
+}
 
-    let name = name.expect("parsed name");
-    let target = target.expect("parsed target");
-
-    Ok(Some((name, translate_go_type_to_rust_type(target, None)?)))
+struct IntConstant {
+    name: String,
+    rust_type: &'static str,
+    value: i64,
+    comments: Vec<String>,
 }
 
-fn parse_struct(pairs: Pairs<Rule>) -> Result<(codegen::Struct, HashSet<String>), Error> {
-    debug!("Parsing struct");
-    let mut name: Option<String> = None;
-    let mut fields: Vec<FieldDef> = Vec::new();
-    let mut comments: Vec<String> = Vec::new();
+// Collects the integer constants of a `const (...)` block, computing values
+// that reference constants defined earlier in the block.
+fn fold_constants(text: &str) -> Vec<IntConstant> {
+    lazy_static! {
+        static ref CONST_RE: Regex =
+            Regex::new(r#"^(\w+)(?:\s+(\w+))?\s*=\s*([^"/]+?)\s*(//.*)?$"#).expect("regex to compile");
+    }
 
-    for pair in pairs {
-        let span = pair.clone().into_span();
-        match pair.as_rule() {
-            Rule::doc_comment => {
-                comments.push(parse_comment(span.as_str()));
+    let body = match (text.find('('), text.rfind(')')) {
+        (Some(start), Some(end)) if start < end => &text[start + 1..end],
+        _ => return vec![],
+    };
+
+    let mut values: HashMap<String, i64> = HashMap::new();
+    let mut constants = vec![];
+    let mut comments = vec![];
+    for line in body.lines() {
+        let line = line.trim();
+        if line.starts_with("//") {
+            comments.push(parse_comment(line));
+            continue;
+        }
+        let caps = match CONST_RE.captures(line) {
+            Some(caps) => caps,
+            None => {
+                comments.clear();
+                continue;
             }
-            Rule::struct_preamble => {
-                name = Some(parse_struct_preamble(pair.into_inner())?);
+        };
+        let rust_type = match caps.get(2).map(|t| t.as_str()) {
+            None | Some("int") | Some("int32") | Some("int64") => "i64",
+            Some("uint") | Some("uint32") | Some("uint64") => "u64",
+            Some(t) => {
+                debug!("Skipping constant {} of type {}", &caps[1], t);
+                comments.clear();
+                continue;
             }
-            Rule::struct_fields => {
-                fields = parse_struct_fields(pair.into_inner())?;
+        };
+        let value = match eval_const_expr(&caps[3], &values) {
+            Some(value) if rust_type == "u64" && value < 0 => None,
+            value => value,
+        };
+        let value = match value {
+            Some(value) => value,
+            None => {
+                warn!("Skipping constant {}: can't compute `{}`", &caps[1], &caps[3]);
+                comments.clear();
+                continue;
             }
-            _ => unreachable!(),
+        };
+        values.insert(caps[1].to_string(), value);
+        let mut constant = IntConstant {
+            name: caps[1].to_string(),
+            rust_type,
+            value,
+            comments: std::mem::take(&mut comments),
+        };
+        if let Some(inline_comment) = caps.get(4) {
+            constant.comments.push(parse_comment(inline_comment.as_str()));
         }
+        constants.push(constant);
     }
+    constants
+}
 
-    let struct_name = name.expect("parsed name");
+// Evaluates integer literals and known constants combined with `+`, `-`, `*`
+// and parentheses.
+fn eval_const_expr(expr: &str, values: &HashMap<String, i64>) -> Option<i64> {
+    lazy_static! {
+        static ref TOKEN_RE: Regex = Regex::new(r"\w+|[-+*()]|\S").expect("regex to compile");
+    }
 
-    let mut rust_struct = Struct::new(&struct_name.to_camel_case());
+    let tokens: Vec<&str> = TOKEN_RE.find_iter(expr).map(|m| m.as_str()).collect();
+    let mut pos = 0;
+    let value = eval_sum(&tokens, &mut pos, values)?;
+    if pos == tokens.len() {
+        Some(value)
+    } else {
+        None
+    }
+}
 
-    // Make it public.
-    rust_struct.vis("pub");
+fn eval_sum(tokens: &[&str], pos: &mut usize, values: &HashMap<String, i64>) -> Option<i64> {
+    let mut value = eval_product(tokens, pos, values)?;
+    while let Some(&op) = tokens.get(*pos) {
+        if op != "+" && op != "-" {
+            break;
+        }
+        *pos += 1;
+        let rhs = eval_product(tokens, pos, values)?;
+        value = if op == "+" { value.checked_add(rhs)? } else { value.checked_sub(rhs)? };
+    }
+    Some(value)
+}
 
-    // Add some derives.
-    rust_struct.derive("Debug");
-    rust_struct.derive("Clone");
-    rust_struct.derive("PartialEq");
-    rust_struct.derive("Deserialize");
-    rust_struct.derive("Serialize");
+fn eval_product(tokens: &[&str], pos: &mut usize, values: &HashMap<String, i64>) -> Option<i64> {
+    let mut value = eval_operand(tokens, pos, values)?;
+    while tokens.get(*pos) == Some(&"*") {
+        *pos += 1;
+        value = value.checked_mul(eval_operand(tokens, pos, values)?)?;
+    }
+    Some(value)
+}
 
-    if !comments.is_empty() {
-        let annotated_comments: Vec<String> = comments
-            .iter_mut()
-            .map(|x| x.replace(&struct_name, &format!("`{}`", &struct_name.to_camel_case())))
-            .collect();
-        rust_struct.doc(&annotated_comments.join("\n"));
+fn eval_operand(tokens: &[&str], pos: &mut usize, values: &HashMap<String, i64>) -> Option<i64> {
+    let token = *tokens.get(*pos)?;
+    *pos += 1;
+    match token {
+        "(" => {
+            let value = eval_sum(tokens, pos, values)?;
+            if tokens.get(*pos) != Some(&")") {
+                return None;
+            }
+            *pos += 1;
+            Some(value)
+        }
+        "-" => eval_operand(tokens, pos, values)?.checked_neg(),
+        _ => token.parse::<i64>().ok().or_else(|| values.get(token).cloned()),
     }
+}
 
-    lazy_static! {
-        static ref HASHMAP_RE: Regex = Regex::new("^HashMap<.+>$").expect("regex to compile");
+fn render_constant(constant: &IntConstant, vis: Visibility) -> String {
+    let mut out = String::new();
+    for comment in &constant.comments {
+        out.push_str(&format!("/// {}\n", comment).replace("/// \n", "///\n"));
     }
+    out.push_str(&format!(
+        "{}const {}: {} = {};",
+        vis.prefix(),
+        constant.name.to_shouty_snake_case(),
+        constant.rust_type,
+        constant.value
+    ));
+    out
+}
 
-    let mut libraries: HashSet<String> = HashSet::new();
+// Type generated for Go's `struct{}`. It is (de)serialized as `{}`, unlike
+// `()` which is `null`.
+const EMPTY_STRUCT_NAME: &str = "Empty";
+
+// XXX: Add tuple struct support to `codegen`
+fn render_newtype(name: &str, target: &str, vis: Visibility) -> String {
+    format!(
+        "#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]\n#[serde(transparent)]\n{}struct {}({}{});",
+        vis.prefix(),
+        name,
+        vis.prefix(),
+        target
+    )
+}
 
-    let mut generics = 0;
+// (De)serializes the entries of a flattened map whose keys start with
+// `prefix`, without the prefix.
+fn render_prefix_map_module(module: &str, prefix: &str) -> String {
+    format!(
+        r#"mod {} {{
+    use serde::de::{{Deserialize, Deserializer, Error as DeError}};
+    use serde::ser::{{SerializeMap, Serializer}};
+    use serde_json::Value;
+    use std::collections::HashMap;
+
+    const PREFIX: &str = "{}";
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {{
+        let mut map = HashMap::new();
+        for (key, value) in HashMap::<String, Value>::deserialize(deserializer)? {{
+            if !key.starts_with(PREFIX) {{
+                continue;
+            }}
+            match value {{
+                Value::String(s) => {{
+                    map.insert(key[PREFIX.len()..].to_string(), s);
+                }}
+                _ => return Err(DeError::custom(format!("expected a string for {{}}", key))),
+            }}
+        }}
+        Ok(map)
+    }}
+
+    pub fn serialize<S>(map: &HashMap<String, String>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {{
+        let mut out = serializer.serialize_map(Some(map.len()))?;
+        for (key, value) in map {{
+            out.serialize_entry(&format!("{{}}{{}}", PREFIX, key), value)?;
+        }}
+        out.end()
+    }}
+}}"#,
+        module, prefix
+    )
+}
 
-    for f in fields {
-        // Translate the name.
-        let member_name = mangle(&f.name.to_snake_case());
+// (De)serializes a `DateTime<Utc>` as a string in a chrono format. Formats
+// without a zone are read as UTC, those without a time of day as midnight.
+fn render_layout_module(module: &str, format: &str) -> String {
+    format!(
+        r#"// A field may only need one of the functions.
+#[allow(dead_code)]
+mod {module} {{
+    use chrono::{{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc}};
+    use serde::de::{{Deserialize, Deserializer, Error as DeError}};
+    use serde::ser::Serializer;
+
+    const FORMAT: &str = "{format}";
+
+    fn parse(s: &str) -> Result<DateTime<Utc>, chrono::ParseError> {{
+        if let Ok(dt) = DateTime::parse_from_str(s, FORMAT) {{
+            return Ok(dt.with_timezone(&Utc));
+        }}
+        if let Ok(dt) = NaiveDateTime::parse_from_str(s, FORMAT) {{
+            return Ok(Utc.from_utc_datetime(&dt));
+        }}
+        let day = NaiveDate::parse_from_str(s, FORMAT)?;
+        Ok(Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).expect("midnight to be valid")))
+    }}
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {{
+        let s = String::deserialize(deserializer)?;
+        parse(&s).map_err(DeError::custom)
+    }}
+
+    pub fn serialize<S>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {{
+        serializer.serialize_str(&value.format(FORMAT).to_string())
+    }}
+
+    pub mod option {{
+        use chrono::{{DateTime, Utc}};
+        use serde::de::{{Deserialize, Deserializer, Error as DeError}};
+        use serde::ser::Serializer;
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {{
+            match Option::<String>::deserialize(deserializer)? {{
+                Some(s) => super::parse(&s).map(Some).map_err(DeError::custom),
+                None => Ok(None),
+            }}
+        }}
+
+        pub fn serialize<S>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {{
+            match *value {{
+                Some(ref v) => super::serialize(v, serializer),
+                None => serializer.serialize_none(),
+            }}
+        }}
+    }}
+}}"#,
+        module = module,
+        format = format.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
 
-        let mut rust_data = translate_go_type_to_rust_type(f.go_type, Some(&mut generics))?;
-        let mut rust_type = rust_data.value;
+// A copy of an `encodings` timestamp for serde's `remote` derive, with the
+// same helpers as the original.
+fn render_remote_timestamp_shim(timestamp: &str, unit: &str) -> String {
+    format!(
+        r#"#[derive(Deserialize, Serialize)]
+#[serde(remote = "{timestamp}")]
+struct {timestamp}Def(
+    #[serde(deserialize_with = "deserialize_{unit}")]
+    #[serde(serialize_with = "serialize_{unit}")]
+    pub DateTime<Utc>,
+);"#,
+        timestamp = timestamp,
+        unit = unit
+    )
+}
 
-        for generic in rust_data.generics {
-            match generic.default {
-                None => {
-                    rust_struct.generic(&generic.value);
-                }
-                Some(default) => {
-                    rust_struct.generic(format!("{}={}", generic.value, default).as_str());
-                }
-            }
+// (De)serializes an `Option` in the `{"<value>": ..., "Valid": ...}` shape
+// of Go's `database/sql` null wrappers.
+fn render_sql_null_module(module: &str, value: &str, value_type: &str) -> String {
+    format!(
+        r#"mod {module} {{
+    use serde::de::{{Deserialize, Deserializer}};
+    use serde::ser::{{SerializeStruct, Serializer}};
+
+    #[derive(Deserialize)]
+    struct Null {{
+        #[serde(rename = "{value}", default)]
+        value: Option<{value_type}>,
+        #[serde(rename = "Valid", default)]
+        valid: bool,
+    }}
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<{value_type}>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {{
+        let null = Option::<Null>::deserialize(deserializer)?;
+        Ok(null.and_then(|n| if n.valid {{ n.value }} else {{ None }}))
+    }}
+
+    pub fn serialize<S>(value: &Option<{value_type}>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {{
+        let mut out = serializer.serialize_struct("Null{value}", 2)?;
+        match *value {{
+            Some(ref v) => out.serialize_field("{value}", v)?,
+            None => out.serialize_field("{value}", &{value_type}::default())?,
+        }}
+        out.serialize_field("Valid", &value.is_some())?;
+        out.end()
+    }}
+}}"#,
+        module = module,
+        value = value,
+        value_type = value_type
+    )
+}
 
-            for bound in generic.bounds {
-                rust_struct.bound(&generic.value, bound);
-            }
-        }
+fn render_empty_struct(vis: Visibility) -> String {
+    format!(
+        "#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]\n{}struct {} {{}}",
+        vis.prefix(),
+        EMPTY_STRUCT_NAME
+    )
+}
 
-        // Extract the code and the libraries from the result.
-        for lib in rust_data.libraries.iter() {
-            libraries.insert(lib.clone());
+// XXX: Add enum variant annotation support to `codegen`
+fn render_untagged_enum(name: &str, candidates: &[String], vis: Visibility) -> String {
+    let mut out = String::new();
+    out.push_str("#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]\n");
+    out.push_str("#[serde(untagged)]\n");
+    out.push_str(&format!("{}enum {} {{\n", vis.prefix(), name));
+    for candidate in candidates {
+        let variant: String = candidate.chars().filter(|c| c.is_alphanumeric()).collect();
+        out.push_str(&format!("    {}({}),\n", variant.to_camel_case(), candidate));
+    }
+    out.push('}');
+    out
+}
+
+// Applies a serde `rename_all` convention to a `PascalCase` variant name.
+fn apply_rename_all(convention: &str, variant: &str) -> Option<String> {
+    let mut snake = String::new();
+    for (i, ch) in variant.char_indices() {
+        if i > 0 && ch.is_uppercase() {
+            snake.push('_');
         }
+        snake.push(ch.to_ascii_lowercase());
+    }
 
-        // Make fields optional if they are optional in the json.
-        if f.omit_empty {
-            // We don't do this for HashMaps as they are handled special below.
-            if !HASHMAP_RE.is_match(&rust_type) {
-                rust_type = format!("Option<{}>", rust_type);
-            }
+    match convention {
+        "lowercase" => Some(variant.to_ascii_lowercase()),
+        "UPPERCASE" => Some(variant.to_ascii_uppercase()),
+        "PascalCase" => Some(variant.to_string()),
+        "camelCase" => {
+            let mut chars = variant.chars();
+            Some(
+                chars
+                    .next()
+                    .map(|first| first.to_lowercase().collect::<String>() + chars.as_str())
+                    .unwrap_or_default(),
+            )
         }
+        "snake_case" => Some(snake),
+        "SCREAMING_SNAKE_CASE" => Some(snake.to_ascii_uppercase()),
+        "kebab-case" => Some(snake.replace('_', "-")),
+        "SCREAMING-KEBAB-CASE" => Some(snake.to_ascii_uppercase().replace('_', "-")),
+        _ => None,
+    }
+}
 
-        if let Some(rename) = f.json_name.clone() {
-            if rename != member_name {
-                rust_data
-                    .annotations
-                    .push(format!("#[serde(rename = \"{}\")]", rename));
+// XXX: Add enum variant annotation support to `codegen`
+fn enum_variant_name(type_name: &str, constant: &str) -> String {
+    // Go prefixes constants with their type, which is redundant in Rust.
+    let stripped = constant.strip_prefix(type_name).unwrap_or("");
+    if stripped.is_empty() || stripped.starts_with(|c: char| c.is_ascii_digit()) {
+        constant.to_camel_case()
+    } else {
+        stripped.to_camel_case()
+    }
+}
+
+fn render_string_enum(
+    name: &str,
+    values: &[EnumValue],
+    config: &CodegenConfig,
+) -> Result<String, CodegenError> {
+    let variants: Vec<(String, &EnumValue)> = values
+        .iter()
+        .map(|v| (enum_variant_name(name, &v.name), v))
+        .collect();
+
+    // Only use `rename_all` when it produces every value.
+    let rename_all = match config.enum_rename_all {
+        Some(ref convention) => {
+            let mut matches = true;
+            for &(ref variant, value) in &variants {
+                match apply_rename_all(convention, variant) {
+                    Some(renamed) => matches = matches && renamed == value.value,
+                    None => {
+                        return Err(CodegenError::Validation(format!(
+                            "unsupported rename_all convention: {}",
+                            convention
+                        )))
+                    }
+                }
+            }
+            if matches {
+                Some(convention)
+            } else {
+                None
             }
         }
+        None => None,
+    };
 
-        if f.embedded {
-            rust_data
-                .annotations
-                .push("#[serde(flatten)]".to_string());
+    let mut out = String::new();
+    out.push_str("#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]\n");
+    if let Some(convention) = rename_all {
+        out.push_str(&format!("#[serde(rename_all = \"{}\")]\n", convention));
+    }
+    out.push_str(&format!("{}enum {} {{\n", config.visibility.prefix(), name.to_camel_case()));
+    for &(ref variant, value) in &variants {
+        for comment in &value.comments {
+            out.push_str(&format!("    /// {}\n", comment).replace("/// \n", "///\n"));
         }
+        if rename_all.is_none() && *variant != value.value {
+            out.push_str(&format!("    #[serde(rename = \"{}\")]\n", value.value));
+        }
+        out.push_str(&format!("    {},\n", variant));
+    }
+    out.push('}');
+
+    if config.emit_enum_all {
+        // XXX: Add associated const support to `codegen`
+        let rust_name = name.to_camel_case();
+        let all: Vec<String> = variants
+            .iter()
+            .map(|(variant, _)| format!("{}::{}", rust_name, variant))
+            .collect();
+        out.push_str(&format!("\n\nimpl {} {{\n", rust_name));
+        out.push_str(&format!(
+            "    {}const ALL: &'static [{}] = &[{}];\n",
+            config.visibility.prefix(),
+            rust_name,
+            all.join(", ")
+        ));
+        out.push('}');
+    }
 
-        let mut field_defs = vec![];
-
-        // Behavior overrides for specific types.
-        if rust_type == "String" {
-            // Go converts null strings to "" and sometimes is wrong about
-            // json string fields that can be `null`. We treat all `String`
-            // fields as `Option<String>` and convert `""` to `None`.
-            libraries.insert("custom_serde::*".to_string());
-
-            let mut string_as_option = Field::new(&member_name, "Option<String>");
-            string_as_option.annotation(vec![
-                "#[serde(deserialize_with = \"deserialize_lambda_string\")]",
-                "#[serde(default)]",
-            ]);
-            field_defs.push(string_as_option);
-        } else if HASHMAP_RE.is_match(&rust_type) {
-            // We default to an empty `HashMap` even if the field is `null`.
-            libraries.insert("custom_serde::*".to_string());
-            let mut map_as_empty = Field::new(&member_name, &rust_type);
-            map_as_empty.annotation(vec![
-                "#[serde(deserialize_with = \"deserialize_lambda_map\")]",
-                "#[serde(default)]",
-            ]);
-            field_defs.push(map_as_empty);
-        } else {
-            field_defs = vec![Field::new(&member_name, &rust_type)];
+    let rust_name = name.to_camel_case();
+    let arms: Vec<(String, String)> = variants
+        .iter()
+        .map(|&(ref variant, value)| (format!("{}::{}", rust_name, variant), value.value.clone()))
+        .collect();
+    let mut impl_scope = Scope::new();
+    if config.emit_enum_kind {
+        impl_scope.push_impl(enum_kind_impl(&rust_name, &arms, config.visibility));
+    }
+    if config.impl_from_str {
+        for imp in from_str_impls(&rust_name, &arms) {
+            impl_scope.push_impl(imp);
         }
+    }
+    if config.emit_enum_kind || config.impl_from_str {
+        out.push_str("\n\n");
+        out.push_str(&impl_scope.to_string());
+    }
 
-        for mut field in field_defs {
-            // Fields are public.
-            field.vis("pub");
+    Ok(out)
+}
 
-            if !f.comments.is_empty() {
-                field.doc(&f.comments.join("\n"));
-            }
+#[derive(Debug, Clone)]
+struct FieldDef {
+    name: String,
+    json_name: Option<String>,
+    comments: Vec<String>,
+    omit_empty: bool,
+    go_type: GoType,
+    embedded: bool,
+    // Go time layout from a `// layout: ...` comment.
+    layout: Option<String>,
+    default_value: Option<String>,
+    // Use `#[serde(default)]` rather than an `Option` for omitted fields.
+    rust_default: bool,
+    // Constant from a `// rust:default_const NAME` comment.
+    default_const: Option<String>,
+    // Name from a `bson:"..."` tag.
+    bson_name: Option<String>,
+    // Hide the value from `Debug`, from a `// rust:redact` comment.
+    redact: bool,
+    // Key prefix from a `// rust:flatten_prefix PREFIX` comment.
+    flatten_prefix: Option<String>,
+    // Keys from `// rust:rename_ser KEY` and `// rust:rename_de KEY` comments.
+    rename_ser: Option<String>,
+    rename_de: Option<String>,
+    // Go types from a `// rust:oneof A B C` comment.
+    oneof: Vec<String>,
+    // Constraints from a `validate` tag, like `required,max=5`.
+    validate: Option<String>,
+    // Declared as `*T`.
+    pointer: bool,
+    // Quoted in JSON, from the `,string` tag option.
+    string_encoded: bool,
+}
 
-            if !rust_data.annotations.is_empty() {
-                let mut all_annotations: Vec<String> = field.get_annotation();
-                let mut new_annotations: Vec<String> = rust_data.annotations.clone();
-                all_annotations.append(&mut new_annotations);
-                field.annotation(all_annotations.iter().map(String::as_str).collect());
+// Collects the import path of each package, keyed by its alias or, without
+// one, the last segment of its path. Blank and dot imports are ignored.
+fn parse_imports(pairs: Pairs<Rule>) -> HashMap<String, String> {
+    let mut imports = HashMap::new();
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::import | Rule::import_multiple => {
+                let mut alias: Option<String> = None;
+                for inner in pair.into_inner() {
+                    match inner.as_rule() {
+                        Rule::import_alias => alias = Some(inner.into_span().as_str().to_string()),
+                        Rule::import_package => {
+                            let path = inner.into_span().as_str().trim_matches('"').to_string();
+                            let name = alias
+                                .take()
+                                .unwrap_or_else(|| path.rsplit('/').next().unwrap_or(&path).to_string());
+                            if name != "_" && name != "." {
+                                imports.insert(name, path);
+                            }
+                        }
+                        _ => unreachable!(),
+                    }
+                }
             }
-
-            rust_struct.push_field(field);
+            _ => {}
         }
     }
+    imports
+}
+
+// Section banners like `// --- S3 Events ---` are kept to preserve the
+// file's organization.
+fn parse_banner(comment: &str) -> Option<String> {
+    lazy_static! {
+        static ref BANNER_RE: Regex =
+            Regex::new(r"^//\s*[-=]{3,}\s*([^-=]*?)\s*[-=]*\s*$").expect("regex to compile");
+    }
+    let caps = BANNER_RE.captures(comment.trim())?;
+    if caps[1].is_empty() {
+        Some("// ===".to_string())
+    } else {
+        Some(format!("// === {} ===", &caps[1]))
+    }
+}
+
+fn parse_comment(c: &str) -> String {
+    c.replacen("//", "", 1).trim().to_string()
+}
 
-    Ok((rust_struct, libraries))
+// Empty block comments like `/**/` or `/* */` document nothing.
+fn is_empty_block_comment(c: &str) -> bool {
+    let c = c.trim();
+    c.len() >= 4 && c.starts_with("/*") && c.ends_with("*/") && c[2..c.len() - 2].trim().is_empty()
 }
 
-fn parse_struct_preamble(pairs: Pairs<Rule>) -> Result<String, Error> {
-    debug!("Parsing struct preamble");
-    let mut name: Option<String> = None;
+// Directives like `//go:nosplit` are for the Go toolchain, not documentation.
+fn is_go_directive(c: &str) -> bool {
+    c.trim_start().starts_with("//go:")
+}
 
+fn parse_type_alias(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+) -> Result<Option<TypeAlias>, CodegenError> {
+    debug!("Parsing type alias");
+    #[cfg(feature = "tracing")]
+    let span = tracing::debug_span!("parse_type_alias", name = tracing::field::Empty).entered();
+    let mut value = None;
     for pair in pairs {
-        let span = pair.clone().into_span();
         match pair.as_rule() {
-            Rule::struct_name => {
-                name = Some(span.as_str().to_string());
+            Rule::local_type_alias => {
+                value = parse_local_type_alias(pair.into_inner(), config)?;
             }
-            _ => unimplemented!(),
+            Rule::package_type_alias => {
+                value = parse_package_type_alias(pair.into_inner(), config)?;
+            }
+            _ => unreachable!(),
         }
     }
-
-    Ok(name.expect("structs always have a name"))
+    #[cfg(feature = "tracing")]
+    {
+        if let Some(ref alias) = value {
+            span.record("name", alias.name.as_str());
+        }
+    }
+    Ok(value)
 }
 
-fn parse_struct_fields(pairs: Pairs<Rule>) -> Result<Vec<FieldDef>, Error> {
-    debug!("Parsing struct fields");
-
-    let mut fields: Vec<FieldDef> = Vec::new();
+fn parse_local_type_alias(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+) -> Result<Option<TypeAlias>, CodegenError> {
+    debug!("Parsing local type alias");
+    let mut name: Option<String> = None;
+    let mut target: Option<GoType> = None;
+    let mut is_alias = false;
+    let mut params: Vec<String> = Vec::new();
 
     for pair in pairs {
+        let span = pair.clone().into_span();
         match pair.as_rule() {
-            Rule::struct_field => fields.push(parse_struct_field(pair.into_inner())?),
-            _ => unimplemented!(),
+            Rule::ident => name = Some(mangle(span.as_str())),
+            Rule::alias_assign => is_alias = true,
+            Rule::type_params => {
+                // Rust doesn't enforce bounds on type aliases, so the
+                // constraints are dropped.
+                params = pair
+                    .into_inner()
+                    .map(|param| param.into_span().as_str().to_camel_case())
+                    .collect();
+            }
+            Rule::type_alias_target => {
+                target = Some(parse_go_type(pair.into_inner(), config, 0)?);
+            }
+            _ => unreachable!(),
         }
     }
 
-    Ok(fields)
+    let name = name.expect("parsed name");
+    let target = target.expect("parsed target");
+    let newtype = config.defined_type_newtypes
+        && !is_alias
+        && params.is_empty()
+        && matches!(
+            target,
+            GoType::StringType
+                | GoType::IntType
+                | GoType::UnsignedIntType
+                | GoType::PlatformIntType
+                | GoType::PlatformUnsignedIntType
+                | GoType::FloatType
+                | GoType::BoolType
+                | GoType::ByteType
+                | GoType::RuneType
+        );
+
+    Ok(Some(TypeAlias {
+        name,
+        target: translate_go_type_to_rust_type(target, None, config, 0)?,
+        from_package: false,
+        newtype,
+        params,
+    }))
 }
 
-fn parse_struct_field(pairs: Pairs<Rule>) -> Result<FieldDef, Error> {
-    debug!("Parsing struct field");
+fn parse_package_type_alias(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+) -> Result<Option<TypeAlias>, CodegenError> {
+    debug!("Parsing package type alias");
     let mut name: Option<String> = None;
-    let mut json: Option<JsonMapping> = None;
-    let mut go_type: Option<GoType> = None;
-    let mut comments: Vec<String> = vec![];
-    let mut is_pointer = false;
-    let mut embedded = false;
+    let mut target: Option<GoType> = None;
+    let mut is_alias = false;
+    let mut import: Option<String> = None;
 
     for pair in pairs {
-        debug!("{:?}", pair);
         let span = pair.clone().into_span();
+        let value = span.as_str();
         match pair.as_rule() {
-            Rule::json_mapping => json = Some(parse_json_mapping(pair.into_inner())?),
-            Rule::doc_comment => comments.push(parse_comment(span.as_str())),
-            Rule::struct_field_decl => {
-                for pair in pair.into_inner() {
-                    let span = pair.clone().into_span();
-                    match pair.as_rule() {
-                        Rule::ident => name = Some(mangle(span.as_str())),
-                        Rule::pointer => is_pointer = true,
-                        Rule::struct_field_type => {
-                            go_type = Some(parse_go_type(pair.into_inner())?)
+            Rule::ident => name = Some(mangle(span.as_str())),
+            Rule::alias_assign => is_alias = true,
+            Rule::package_ident => {
+                target = Some(match parse_go_package_ident(value, config) {
+                    // An alias of another package's type refers to the type
+                    // generated for it.
+                    Err(CodegenError::UnsupportedType(_)) if is_alias => {
+                        let (package, type_name) = value.split_at(value.rfind('.').expect("package separator"));
+                        let type_name = &type_name[1..];
+                        if let Some(path) = config.alias_import_paths.get(package) {
+                            import = Some(format!("{}::{}", path, type_name.to_camel_case()));
                         }
-                        Rule::struct_embedded_field => {
-                            info!("struct_embedded_field found: {:?}", pair);
-                            let value = pair.clone().into_span().as_str();
-                            name = Some(mangle(value));
-                            go_type = Some(parse_go_type(pair.into_inner())?);
-                            embedded = true;
-                        },
-                        rule @ _ => panic!("invalid Rule found in struct_field_decl: {:?}", rule),
+                        GoType::UserDefined(type_name.to_string())
                     }
-                }
+                    other => other?,
+                });
             }
-            _ => unimplemented!(),
+            _ => unreachable!(),
         }
     }
 
-    let json_name = if let Some(j) = json.clone() {
-        Some(j.name)
-    } else {
-        None
-    };
+    let name = name.expect("parsed name");
+    let target = target.expect("parsed target");
 
-    let mut omit_empty = if let Some(j) = json.clone() {
-        // We omit empty (aka use an Option) if the JSON says so.
-        j.omit_empty
-    } else {
-        // By default we don't omit empty.
-        false
-    };
+    let mut target = translate_go_type_to_rust_type(target, None, config, 0)?;
+    target.libraries.extend(import);
+    Ok(Some(TypeAlias {
+        name,
+        target,
+        from_package: true,
+        newtype: false,
+        params: vec![],
+    }))
+}
 
-    if is_pointer {
-        // If given a pointer, it can be `nil` and essentially empty.
-        omit_empty = true
+struct ParsedStruct {
+    name: String,
+    go_name: String,
+    // Keys of the non-flattened fields.
+    keys: HashSet<String>,
+    // Types of the flattened fields, in source order.
+    embedded: Vec<String>,
+    rust_struct: codegen::Struct,
+    libraries: HashSet<String>,
+    // Helpers referenced by the struct's serde attributes.
+    functions: Vec<codegen::Function>,
+    // Rendered items used by the struct's fields.
+    raw_items: Vec<String>,
+    // Attributes for the struct itself, like serde container attributes.
+    attributes: Vec<String>,
+    // Attributes that must precede the derives, like `#[serde_as]`.
+    pre_derive_attributes: Vec<String>,
+    impls: Vec<codegen::Impl>,
+    // Structs generated for anonymous struct fields.
+    nested: Vec<ParsedStruct>,
+}
+
+// What other structs in the file imply for the one being generated.
+#[derive(Default)]
+struct StructContext {
+    // Structs that need a lifetime for their borrowed strings.
+    borrowed: HashSet<String>,
+    // Structs holding a value that can't be compared.
+    without_partial_eq: HashSet<String>,
+    // Structs holding a value that can't be ordered.
+    without_ord: HashSet<String>,
+    // Structs holding a value that can't be hashed.
+    without_hash: HashSet<String>,
+    // Rust paths of the generated constants, keyed by their Go name.
+    constants: HashMap<String, String>,
+    // Camel cased names of the type aliases of slices.
+    slice_aliases: HashSet<String>,
+    // Names of the structs in the file and those generated for anonymous
+    // structs so far.
+    type_names: RefCell<HashSet<String>>,
+}
+
+impl StructContext {
+    // Claims a name for an anonymous struct, adding a numeric suffix when it
+    // is already taken.
+    fn claim_type_name(&self, base: &str) -> String {
+        let mut names = self.type_names.borrow_mut();
+        let mut name = base.to_string();
+        let mut suffix = 2;
+        while names.contains(&name) {
+            name = format!("{}{}", base, suffix);
+            suffix += 1;
+        }
+        names.insert(name.clone());
+        name
     }
+}
 
-    // Parse inline comment after json definition.
-    if let Some(j) = json {
-        if let Some(inline_comment) = j.comment {
-            if !comments.is_empty() {
-                // Append inline comment with a blank comment line before it.
-                comments.push("".to_string());
+fn parse_struct_context(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+) -> Result<StructContext, CodegenError> {
+    let mut context = StructContext::default();
+    for pair in pairs.clone() {
+        if pair.as_rule() != Rule::struct_def {
+            continue;
+        }
+        for pair in pair.into_inner() {
+            if pair.as_rule() == Rule::struct_preamble {
+                let name = parse_struct_preamble(pair.into_inner())?;
+                context.type_names.borrow_mut().insert(name.to_camel_case());
             }
-            comments.push(inline_comment)
         }
-    };
+    }
+    if config.omitempty_slice_aliases {
+        for pair in pairs.clone() {
+            if pair.as_rule() != Rule::type_alias {
+                continue;
+            }
+            if let Some(alias) = parse_type_alias(pair.into_inner(), config)? {
+                if !alias.from_package && alias.target.value.starts_with("Vec<") {
+                    context.slice_aliases.insert(alias.name.to_camel_case());
+                }
+            }
+        }
+    }
+    if !config.borrowed_strings && !config.raw_json_values && !config.derive_ord && !config.ordered_floats {
+        return Ok(context);
+    }
 
-    Ok(FieldDef {
-        name: name.expect("fields have names"),
-        json_name,
-        comments,
-        omit_empty,
-        go_type: go_type.expect("fields have types"),
-        embedded,
-    })
+    let mut structs: Vec<(String, Vec<FieldDef>)> = Vec::new();
+    for pair in pairs {
+        if pair.as_rule() != Rule::struct_def {
+            continue;
+        }
+        let mut name = None;
+        let mut fields = vec![];
+        for pair in pair.into_inner() {
+            match pair.as_rule() {
+                Rule::struct_preamble => name = Some(parse_struct_preamble(pair.into_inner())?),
+                Rule::struct_fields => fields = parse_struct_fields(pair.into_inner(), config)?,
+                _ => {}
+            }
+        }
+        structs.push((name.expect("parsed name").to_camel_case(), fields));
+    }
+
+    if config.borrowed_strings {
+        // Only fields that would otherwise be a plain `String`.
+        context.borrowed = transitive_structs(&structs, &|f| {
+            !f.omit_empty && matches!(f.go_type, GoType::StringType)
+        });
+    }
+    if config.raw_json_values {
+        context.without_partial_eq = transitive_structs(&structs, &|f| contains_json_raw(&f.go_type));
+    }
+    if config.derive_ord {
+        let names = context.type_names.borrow().clone();
+        context.without_ord =
+            transitive_structs(&structs, &|f| !is_ord(&f.go_type, config, &|name| names.contains(name)));
+    }
+    if config.ordered_floats {
+        let names = context.type_names.borrow().clone();
+        context.without_hash =
+            transitive_structs(&structs, &|f| !is_hash(&f.go_type, config, &|name| names.contains(name)));
+    }
+
+    Ok(context)
 }
 
-#[derive(Debug, Clone)]
-struct JsonMapping {
-    name: String,
-    comment: Option<String>,
-    omit_empty: bool,
+// Finds the structs with a field matching `direct`, either themselves or
+// through other structs defined in the same file.
+fn transitive_structs(
+    structs: &[(String, Vec<FieldDef>)],
+    direct: &dyn Fn(&FieldDef) -> bool,
+) -> HashSet<String> {
+    let mut found: HashSet<String> = HashSet::new();
+    loop {
+        let mut changed = false;
+        for (name, fields) in structs {
+            if found.contains(name) {
+                continue;
+            }
+            if fields
+                .iter()
+                .any(|f| direct(f) || references_any(&f.go_type, &found))
+            {
+                found.insert(name.clone());
+                changed = true;
+            }
+        }
+        if !changed {
+            return found;
+        }
+    }
 }
 
-fn parse_json_mapping(pairs: Pairs<Rule>) -> Result<JsonMapping, Error> {
-    debug!("Parsing json mapping");
-    let mut name: Option<String> = None;
-    let mut comment: Option<String> = None;
-    let mut omit_empty = false;
+fn contains_json_raw(go_type: &GoType) -> bool {
+    match *go_type {
+        GoType::JsonRawType => true,
+        GoType::ArrayType(ref v) | GoType::FixedArrayType(ref v, _) | GoType::PointerType(ref v) => {
+            contains_json_raw(v)
+        }
+        GoType::MapType(ref k, ref v) => contains_json_raw(k) || contains_json_raw(v),
+        _ => false,
+    }
+}
 
-    for pair in pairs {
-        debug!("{:?}", pair);
-        let span = pair.clone().into_span();
-        match pair.as_rule() {
-            Rule::json_name => name = Some(span.as_str().to_string()),
-            Rule::any_comment => comment = Some(parse_comment(span.as_str())),
-            Rule::omit_empty => omit_empty = true,
-            _ => unimplemented!(),
+// Whether the Rust type of `go_type` implements `Ord`. `is_ord_struct` tells
+// for the camel cased names of user defined types.
+fn is_ord(go_type: &GoType, config: &CodegenConfig, is_ord_struct: &dyn Fn(&str) -> bool) -> bool {
+    match *go_type {
+        GoType::StringType
+        | GoType::IntType
+        | GoType::UnsignedIntType
+        | GoType::PlatformIntType
+        | GoType::PlatformUnsignedIntType
+        | GoType::BoolType
+        | GoType::ByteType
+        | GoType::RuneType
+        | GoType::CodePointsType
+        | GoType::TimeType
+        | GoType::TimestampMillisecondsType
+        | GoType::TimestampSecondsType
+        | GoType::UuidType => true,
+        GoType::FloatType => config.ordered_floats,
+        GoType::SqlNullType(ref value) => value != "Float64",
+        // `[]byte` is `Base64Data`, which isn't.
+        GoType::ArrayType(ref v) if matches!(**v, GoType::ByteType) => false,
+        GoType::ArrayType(ref v) | GoType::FixedArrayType(ref v, _) | GoType::PointerType(ref v) => {
+            is_ord(v, config, is_ord_struct)
         }
+        GoType::UserDefined(ref x) => is_ord_struct(&x.to_camel_case()),
+        GoType::AnonymousStruct(ref fields) => fields.iter().all(|f| is_ord(&f.go_type, config, is_ord_struct)),
+        GoType::MapType(ref k, ref v) => {
+            config.map_type == MapType::BTreeMap && is_ord(k, config, is_ord_struct) && is_ord(v, config, is_ord_struct)
+        }
+        _ => false,
     }
+}
 
-    Ok(JsonMapping {
-        name: name.expect("json mappings always have a name"),
-        comment,
-        omit_empty,
-    })
+// Like `is_ord`, though the `encodings` timestamps aren't `Hash` either.
+fn is_hash(go_type: &GoType, config: &CodegenConfig, is_hash_struct: &dyn Fn(&str) -> bool) -> bool {
+    match *go_type {
+        GoType::TimestampMillisecondsType | GoType::TimestampSecondsType => false,
+        GoType::ArrayType(ref v) if matches!(**v, GoType::ByteType) => false,
+        GoType::ArrayType(ref v) | GoType::FixedArrayType(ref v, _) | GoType::PointerType(ref v) => {
+            is_hash(v, config, is_hash_struct)
+        }
+        GoType::AnonymousStruct(ref fields) => fields.iter().all(|f| is_hash(&f.go_type, config, is_hash_struct)),
+        GoType::MapType(ref k, ref v) => {
+            config.map_type == MapType::BTreeMap && is_hash(k, config, is_hash_struct) && is_hash(v, config, is_hash_struct)
+        }
+        _ => is_ord(go_type, config, is_hash_struct),
+    }
 }
 
-#[derive(Debug, Clone)]
-enum GoType {
-    StringType,
-    IntType,
-    UnsignedIntType,
-    FloatType,
-    BoolType,
-    ByteType,
-    UserDefined(String),
-    ArrayType(Box<GoType>),
-    MapType(Box<GoType>, Box<GoType>),
-    InterfaceType,
-    PointerType(Box<GoType>),
-    TimeType,
-    TimestampMillisecondsType,
-    TimestampSecondsType,
-    JsonRawType,
+fn contains_empty_struct(go_type: &GoType) -> bool {
+    match *go_type {
+        GoType::EmptyStructType => true,
+        GoType::ArrayType(ref v) | GoType::FixedArrayType(ref v, _) | GoType::PointerType(ref v) => {
+            contains_empty_struct(v)
+        }
+        GoType::MapType(ref k, ref v) => contains_empty_struct(k) || contains_empty_struct(v),
+        _ => false,
+    }
 }
 
-struct RustType {
-    annotations: Vec<String>,
-    libraries: HashSet<String>,
-    value: String,
-    generics: Vec<RustGeneric>,
+fn references_any(go_type: &GoType, names: &HashSet<String>) -> bool {
+    match *go_type {
+        GoType::UserDefined(ref x) => names.contains(&x.to_camel_case()),
+        GoType::ArrayType(ref v) | GoType::FixedArrayType(ref v, _) | GoType::PointerType(ref v) => {
+            references_any(v, names)
+        }
+        GoType::MapType(ref k, ref v) => references_any(k, names) || references_any(v, names),
+        _ => false,
+    }
 }
 
-#[derive(Clone)]
-struct RustGeneric {
-    value: String,
-    default: Option<String>,
-    bounds: Vec<String>,
+// Adds the `'a` lifetime to references of borrowing structs.
+fn add_lifetimes(rust_type: &str, borrowed: &HashSet<String>) -> String {
+    let mut result = rust_type.to_string();
+    for name in borrowed {
+        let re = Regex::new(&format!(r"\b{}\b", regex::escape(name))).expect("regex to compile");
+        result = re.replace_all(&result, format!("{}<'a>", name).as_str()).into_owned();
+    }
+    result
 }
 
-fn parse_go_type(pairs: Pairs<Rule>) -> Result<GoType, Error> {
-    debug!("Parsing go type");
-    let mut go_type: Option<GoType> = None;
+fn parse_struct(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+    context: &StructContext,
+) -> Result<ParsedStruct, CodegenError> {
+    debug!("Parsing struct");
+    let mut name: Option<String> = None;
+    let mut fields: Vec<FieldDef> = Vec::new();
+    let mut comments: Vec<String> = Vec::new();
 
     for pair in pairs {
-        debug!("{:?}", pair);
-        let value = pair.clone().into_span().as_str();
-        go_type = match pair.as_rule() {
-            Rule::array => Some(parse_go_type_array(pair.into_inner())?),
-            Rule::primitive => Some(parse_go_type_primitive(value)?),
-            Rule::ident => Some(parse_go_ident(value)?),
-            Rule::package_ident => Some(parse_go_package_ident(value)?),
-            Rule::map => Some(parse_go_type_map(pair.into_inner())?),
-            Rule::interface => Some(parse_go_type_interface(value)?),
-            Rule::pointer_type => Some(parse_go_type_pointer(pair.into_inner())?),
-            _ => unimplemented!("{}\n{}", value, pair),
-        };
+        let span = pair.clone().into_span();
+        match pair.as_rule() {
+            Rule::doc_comment if is_empty_block_comment(span.as_str()) => {}
+            Rule::doc_comment => {
+                comments.push(parse_comment(span.as_str()));
+            }
+            Rule::struct_preamble => {
+                name = Some(parse_struct_preamble(pair.into_inner())?);
+            }
+            Rule::struct_fields => {
+                fields = parse_struct_fields(pair.into_inner(), config)?;
+            }
+            _ => unreachable!(),
+        }
     }
 
-    Ok(go_type.expect("parsing go type"))
+    build_struct(name.expect("parsed name"), comments, fields, config, context)
 }
 
-fn parse_go_type_array(pairs: Pairs<Rule>) -> Result<GoType, Error> {
-    debug!("Parsing go array");
-    let mut go_type: Option<GoType> = None;
-
-    for pair in pairs {
-        debug!("{:?}", pair);
-        let value = pair.clone().into_span().as_str();
-        go_type = match pair.as_rule() {
-            Rule::primitive => Some(GoType::ArrayType(Box::new(parse_go_type_primitive(value)?))),
-            Rule::ident => Some(GoType::ArrayType(Box::new(GoType::UserDefined(
-                value.to_string(),
-            )))),
-            Rule::map => Some(GoType::ArrayType(Box::new(parse_go_type_map(
-                pair.into_inner(),
-            )?))),
-            Rule::array => Some(GoType::ArrayType(Box::new(parse_go_type_array(
-                pair.into_inner(),
-            )?))),
-            _ => unimplemented!(),
+fn build_struct(
+    struct_name: String,
+    mut comments: Vec<String>,
+    mut fields: Vec<FieldDef>,
+    config: &CodegenConfig,
+    context: &StructContext,
+) -> Result<ParsedStruct, CodegenError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("parse_struct", name = %struct_name).entered();
+
+    // Checked before anonymous structs are replaced by their generated ones.
+    let ord = config.derive_ord && !config.capture_extra && fields.iter().all(|f| {
+        is_ord(&f.go_type, config, &|name| {
+            context.type_names.borrow().contains(name) && !context.without_ord.contains(name)
+        })
+    });
+    // `OrderedFloat` is what lets structs with floats be `Hash` at all.
+    let hash = config.ordered_floats
+        && !config.capture_extra
+        && !context.without_partial_eq.contains(&struct_name.to_camel_case())
+        && fields.iter().all(|f| {
+            is_hash(&f.go_type, config, &|name| {
+                context.type_names.borrow().contains(name) && !context.without_hash.contains(name)
+            })
+        });
+
+    // Anonymous structs are generated separately, named after their path.
+    let mut nested: Vec<ParsedStruct> = Vec::new();
+    for f in &mut fields {
+        let inner = match f.go_type {
+            GoType::AnonymousStruct(ref inner) => inner.clone(),
+            _ => continue,
         };
+        let nested_name =
+            context.claim_type_name(&format!("{}{}", struct_name.to_camel_case(), f.name.to_camel_case()));
+        nested.push(build_struct(nested_name.clone(), vec![], inner, config, context)?);
+        f.go_type = GoType::UserDefined(nested_name);
     }
 
-    Ok(go_type.expect("parsing go array"))
-}
-
-fn parse_go_type_map(pairs: Pairs<Rule>) -> Result<GoType, Error> {
-    debug!("Parsing go map");
-    let mut key_type: Option<GoType> = None;
-    let mut value_type: Option<GoType> = None;
+    let mut rust_struct = Struct::new(&struct_name.to_camel_case());
 
-    for pair in pairs {
-        debug!("{:?}", pair);
-        let value = pair.clone().into_span().as_str();
-        match pair.as_rule() {
-            Rule::key_type => key_type = Some(parse_go_type_primitive(value)?),
-            Rule::value_type => value_type = Some(parse_go_type(pair.into_inner())?),
-            _ => unimplemented!(),
-        };
+    if let Some(vis) = config.visibility.keyword() {
+        rust_struct.vis(vis);
     }
 
-    Ok(GoType::MapType(
-        Box::new(key_type.expect("parsing map key")),
-        Box::new(value_type.expect("parsing map value")),
-    ))
-}
+    if config.field_naming != FieldNaming::SnakeCase {
+        rust_struct.allow("non_snake_case");
+    }
 
-fn parse_go_type_interface(_t: &str) -> Result<GoType, Error> {
-    // For now we don't parse.
-    Ok(GoType::InterfaceType)
-}
+    // Redacted fields need a hand-written `Debug`.
+    let redacted = fields.iter().any(|f| f.redact);
 
-fn parse_go_type_pointer(pairs: Pairs<Rule>) -> Result<GoType, Error> {
-    debug!("Parsing go pointer");
-    let mut pointed_at = None;
-    for pair in pairs {
-        debug!("{:?}", pair);
-        match pair.as_rule() {
-            Rule::pointer => (),
-            Rule::value_type => pointed_at = Some(parse_go_type(pair.into_inner())?),
-            _ => unimplemented!(),
-        };
+    // Add some derives.
+    if !redacted {
+        rust_struct.derive("Debug");
     }
-    Ok(GoType::PointerType(Box::new(pointed_at.expect("something pointed at"))))
-}
-
-fn parse_go_type_primitive(t: &str) -> Result<GoType, Error> {
-    match t {
-        "string" => Ok(GoType::StringType),
-        "int" | "int32" | "int64" => Ok(GoType::IntType),
-        "uint" | "uint32" | "uint64" => Ok(GoType::UnsignedIntType),
-        "float" | "float32" | "float64" => Ok(GoType::FloatType),
-        "bool" => Ok(GoType::BoolType),
-        "byte" => Ok(GoType::ByteType),
-        _ => unimplemented!("missing go type primitive"),
+    rust_struct.derive("Clone");
+    if !context.without_partial_eq.contains(&struct_name.to_camel_case()) {
+        rust_struct.derive("PartialEq");
     }
-}
-
-fn parse_go_ident(t: &str) -> Result<GoType, Error> {
-    match t {
-        "MilliSecondsEpochTime" => Ok(GoType::TimestampMillisecondsType),
-        "SecondsEpochTime" => Ok(GoType::TimestampSecondsType),
-        _ => Ok(GoType::UserDefined(t.to_string())),
+    if ord {
+        rust_struct.derive("Eq");
+        rust_struct.derive("PartialOrd");
+        rust_struct.derive("Ord");
+    } else if config.derive_ord {
+        warn!("Not deriving Ord for {}: not all of its fields can be ordered", struct_name);
     }
-}
-
-fn parse_go_package_ident(t: &str) -> Result<GoType, Error> {
-    match t {
-        "time.Time" => Ok(GoType::TimeType),
-        "json.RawMessage" => Ok(GoType::JsonRawType),
-        _ => unimplemented!("missing go package ident mapping"),
+    if hash {
+        if !ord {
+            rust_struct.derive("Eq");
+        }
+        rust_struct.derive("Hash");
     }
-}
+    rust_struct.derive("Deserialize");
+    rust_struct.derive("Serialize");
 
-fn mangle(s: &str) -> String {
-    // TODO: Add more keywords.
-    match s {
-        "ref" => "ref_".to_string(),
-        "type" => "type_".to_string(),
-        _ => s.to_string(),
+    if let Some(doc) = config.doc_overrides.get(&struct_name) {
+        rust_struct.doc(doc);
+    } else if !comments.is_empty() {
+        let annotated_comments: Vec<String> = comments
+            .iter_mut()
+            .map(|x| x.replace(&struct_name, &format!("`{}`", &struct_name.to_camel_case())))
+            .collect();
+        rust_struct.doc(&annotated_comments.join("\n"));
     }
-}
 
-fn make_rust_type_with_no_libraries(value: &str) -> RustType {
-    RustType {
-        annotations: vec![],
-        value: value.to_string(),
-        generics: vec![],
-        libraries: HashSet::new(),
+    lazy_static! {
+        static ref MAP_RE: Regex =
+            Regex::new("^(HashMap|BTreeMap|IndexMap)<.+>$").expect("regex to compile");
     }
-}
 
-fn translate_go_type_to_rust_type(go_type: GoType, generic_counter: Option<&mut usize>) -> Result<RustType, Error> {
-    let rust_type = match &go_type {
-        GoType::StringType => make_rust_type_with_no_libraries("String"),
-        GoType::BoolType => make_rust_type_with_no_libraries("bool"),
-        GoType::ByteType => make_rust_type_with_no_libraries("u8"),
-        GoType::IntType => make_rust_type_with_no_libraries("i64"),
-        GoType::UnsignedIntType => make_rust_type_with_no_libraries("u64"),
-        GoType::FloatType => make_rust_type_with_no_libraries("f64"),
-        GoType::UserDefined(x) => make_rust_type_with_no_libraries(&x.to_camel_case()),
-        GoType::ArrayType(x) => {
-            let mut i = translate_go_type_to_rust_type(*x.clone(), generic_counter)?;
-            
-            if i.value == "u8" {
-                let mut libraries = i.libraries.clone();
-                libraries.insert("super::super::encodings::Base64Data".to_string());
-                // Handle []u8 special, as it is base64 encoded.
-                RustType {
-                    annotations: i.annotations,
-                    value: "Base64Data".to_string(),
-                    generics: i.generics,
-                    libraries: libraries,
-                }
-            } else {
-                RustType {
-                    annotations: i.annotations,
-                    value: format!("Vec<{}>", i.value),
-                    generics: i.generics,
-                    libraries: i.libraries,
-                }
-            }
-        },
-        GoType::PointerType(v) => {
-            let data = translate_go_type_to_rust_type(*v.clone(), generic_counter)?;
-            let libraries: HashSet<String> = data.libraries.iter().cloned().collect();
-            RustType {
-                annotations: data.annotations,
-                value: format!("Option<{}>", data.value),
-                generics: data.generics,
-                libraries,
-            }
-        },
-        GoType::MapType(k, v) => {
-            // TODO can we use a ref to the option to save this dance?
-            let mut generics = 0;
+    let mut libraries: HashSet<String> = HashSet::new();
+    let mut functions: Vec<codegen::Function> = Vec::new();
+    let mut raw_items: Vec<String> = Vec::new();
+    let mut keys: HashSet<String> = HashSet::new();
+    let mut embedded: Vec<String> = Vec::new();
+    // Pushed after the fields so the lifetime can go first.
+    let mut struct_generics: Vec<String> = Vec::new();
+    let mut generic_bounds: Vec<(String, String)> = Vec::new();
+    // Generics from the translated types, declared if a field still uses them.
+    let mut field_generics: Vec<RustGeneric> = Vec::new();
+    let mut field_types: Vec<String> = Vec::new();
+    // Field names and whether they are redacted, for a hand-written `Debug`.
+    let mut debug_fields: Vec<(String, bool)> = Vec::new();
+    // `Base64Data` fields and whether they are optional.
+    let mut base64_fields: Vec<(String, bool)> = Vec::new();
+    // Checks for the `validate` method, in field order.
+    let mut validations: Vec<String> = Vec::new();
+    // Whether a field uses a `#[serde_as]` annotation.
+    let mut serde_as = false;
 
-            if let Some(ref generic_counter) = generic_counter {
-                generics = **generic_counter;
-            }
+    let mut generics = 0;
 
-            let key_data = translate_go_type_to_rust_type(*k.clone(), Some(&mut generics))?;
-            let value_data = translate_go_type_to_rust_type(*v.clone(), Some(&mut generics))?;
+    for mut f in fields {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("field", name = %f.name).entered();
 
-            if let Some(mut generic_counter) = generic_counter {
-                *generic_counter = generics;
-            }
+        // Translate the name.
+        let member_name = field_member_name(&f, config.field_naming);
 
-            let mut annotations = Vec::new();
-            annotations.extend(key_data.annotations);
-            annotations.extend(value_data.annotations);
+        if let Some(prefix) = f.flatten_prefix.clone() {
+            // Collects the keys with the prefix, whatever the Go type.
+            let module = format!("prefix_map_{}", prefix.to_snake_case());
+            libraries.insert("std::collections::HashMap".to_string());
+            raw_items.push(render_prefix_map_module(&module, &prefix));
+            let mut field = Field::new(&member_name, "HashMap<String, String>");
+            if let Some(vis) = config.visibility.keyword() {
+                field.vis(vis);
+            }
+            if !f.comments.is_empty() {
+                field.doc(&f.comments.join("\n"));
+            }
+            let annotation = format!("#[serde(flatten, with = \"{}\")]", module);
+            field.annotation(vec![&annotation]);
+            rust_struct.push_field(field);
+            debug_fields.push((member_name, f.redact));
+            continue;
+        }
 
-            let mut generics = Vec::new();
-            generics.extend(key_data.generics);
-            generics.extend(value_data.generics);
+        let is_time = matches!(f.go_type, GoType::TimeType);
+        let is_bool = matches!(f.go_type, GoType::BoolType);
+        let is_number = matches!(
+            f.go_type,
+            GoType::IntType
+                | GoType::UnsignedIntType
+                | GoType::PlatformIntType
+                | GoType::PlatformUnsignedIntType
+                | GoType::FloatType
+        );
+        let is_optional_time = match f.go_type {
+            GoType::TimeType => f.omit_empty,
+            GoType::PointerType(ref t) => matches!(**t, GoType::TimeType),
+            _ => false,
+        };
+        let is_big_int = matches!(f.go_type, GoType::BigIntType);
+        let is_code_points = matches!(f.go_type, GoType::CodePointsType);
+        let is_slice_alias = config.omitempty_slice_aliases
+            && f.omit_empty
+            && !f.pointer
+            && match f.go_type {
+                GoType::UserDefined(ref x) => context.slice_aliases.contains(&x.to_camel_case()),
+                _ => false,
+            };
+        let sql_null = match f.go_type {
+            GoType::SqlNullType(ref value) => Some(value.clone()),
+            _ => None,
+        };
+        let epoch_timestamp = match f.go_type {
+            GoType::TimestampSecondsType => Some(("SecondTimestamp", "seconds")),
+            GoType::TimestampMillisecondsType => Some(("MillisecondTimestamp", "milliseconds")),
+            _ => None,
+        };
+        // The helpers only produce 64 bit integers.
+        let wide_ints = config.platform_int_bits != 32;
+        let integer_helper = match f.go_type {
+            GoType::IntType => Some("deserialize_lambda_i64"),
+            GoType::UnsignedIntType => Some("deserialize_lambda_u64"),
+            GoType::PlatformIntType if wide_ints => Some("deserialize_lambda_i64"),
+            GoType::PlatformUnsignedIntType if wide_ints => Some("deserialize_lambda_u64"),
+            _ => None,
+        };
+        // Only Go strings, not other types that happen to map to `String`.
+        let shared = config
+            .shared_fields
+            .get(&FieldPath::new(&struct_name, &f.name))
+            .copied();
+        let is_go_string = matches!(f.go_type, GoType::StringType);
+        let is_string = is_go_string && !f.omit_empty && shared.is_none();
+        let string_type = if config.strings_as_boxed_str { "Box<str>" } else { "String" };
+
+        // A `// rust:oneof` comment only applies without a configured list.
+        let oneof: Vec<String> = f.oneof.iter().map(|t| t.to_camel_case()).collect();
+        let candidates = match f.go_type {
+            GoType::InterfaceType => config
+                .untagged_interfaces
+                .get(&FieldPath::new(&struct_name, &f.name))
+                .or(Some(&oneof).filter(|oneof| !oneof.is_empty())),
+            _ => None,
+        };
 
-            let mut libraries = HashSet::new();
-            libraries.extend(key_data.libraries);
-            libraries.extend(value_data.libraries);
-            libraries.insert("std::collections::HashMap".to_string());
+        if contains_empty_struct(&f.go_type) {
+            raw_items.push(render_empty_struct(config.visibility));
+        }
 
-            RustType {
-                value: format!("HashMap<{}, {}>", key_data.value, value_data.value),
-                annotations,
-                generics,
-                libraries,
+        let mut rust_data = match candidates {
+            Some(candidates) => {
+                let enum_name = format!("{}{}", struct_name.to_camel_case(), f.name.to_camel_case());
+                raw_items.push(render_untagged_enum(&enum_name, candidates, config.visibility));
+                make_rust_type_with_no_libraries(&enum_name)
             }
+            None => translate_go_type_to_rust_type(f.go_type, Some(&mut generics), config, 0)?,
+        };
+        let mut rust_type = rust_data.value;
+        let base_type = rust_type.clone();
+        if !context.borrowed.is_empty() {
+            rust_type = add_lifetimes(&rust_type, &context.borrowed);
+        }
+        if is_go_string && config.strings_as_boxed_str && !config.borrowed_strings {
+            rust_type = string_type.to_string();
+        }
+        if let Some(pointer) = shared {
+            libraries.insert(pointer.path().to_string());
+            rust_type = format!("{}<{}>", pointer.name(), rust_type);
         }
-        // For now we treat interfaces as a generic JSON value and make callers
-        // deal with it.
-        GoType::InterfaceType | GoType::JsonRawType => {
-            let mut libraries = HashSet::new();
-            libraries.insert("serde_json::Value".to_string());
 
-            match generic_counter {
-                Some(mut counter) => {
-                    *counter = *counter + 1;
-                    let next_generic = format!("T{}", counter);
+        field_generics.extend(rust_data.generics);
 
-                    libraries.insert("serde::de::DeserializeOwned".to_string());
-                    libraries.insert("serde::ser::Serialize".to_string());
+        // Extract the code and the libraries from the result.
+        for lib in rust_data.libraries.iter() {
+            libraries.insert(lib.clone());
+        }
 
-                    RustType {
-                        annotations: vec!["#[serde(bound=\"\")]".to_string()],
-                        value: next_generic.clone(),
-                        generics: vec![RustGeneric {
-                            value: next_generic.clone(),
-                            default: Some("Value".to_string()),
-                            bounds: vec![
-                                "DeserializeOwned".to_string(),
-                                "Serialize".to_string(),
-                            ],
-                        }],
-                        libraries,
-                    }
-                }
-                None => {
-                    RustType {
-                        annotations: vec![],
-                        value: "Value".to_string(),
-                        generics: vec![],
-                        libraries,
-                    }
-                }
+        // Make fields optional if they are optional in the json. Pointers
+        // are `omit_empty` too, so this is their only `Option`.
+        let mut made_optional = false;
+        if f.omit_empty && !f.rust_default && sql_null.is_none() && !is_slice_alias {
+            // We don't do this for maps as they are handled special below,
+            // unless they are behind a pointer.
+            if !MAP_RE.is_match(&rust_type) || f.pointer {
+                rust_type = format!("Option<{}>", rust_type);
+                made_optional = true;
             }
         }
-        GoType::TimestampSecondsType => {
-            let mut libraries = HashSet::new();
-            libraries.insert("super::super::encodings::SecondTimestamp".to_string());
-            RustType {
-                annotations: vec![],
-                value: "SecondTimestamp".to_string(),
-                generics: vec![],
-                libraries,
-            }
+        if made_optional && rust_data.annotations.iter().any(|a| a == BIG_ARRAY_ANNOTATION) {
+            return Err(CodegenError::UnsupportedType(format!(
+                "array of more than 32 elements in optional field {}",
+                f.name
+            )));
         }
-        GoType::TimestampMillisecondsType => {
-            let mut libraries = HashSet::new();
-            libraries.insert("super::super::encodings::MillisecondTimestamp".to_string());
 
-            RustType {
-                annotations: vec![],
-                value: "MillisecondTimestamp".to_string(),
-                generics: vec![],
-                libraries,
+        if config.base64_accessors {
+            if rust_type == "Base64Data" {
+                base64_fields.push((member_name.clone(), false));
+            } else if rust_type == "Option<Base64Data>" {
+                base64_fields.push((member_name.clone(), true));
             }
         }
-        GoType::TimeType => {
-            // No need for custom deserialization as Go's time.Time type
-            // deserializes to chrono's default format. Neat.
-            let mut libraries = HashSet::new();
+
+        // Fields with a `default:"..."` tag or a `rust:default_const`
+        // directive get a function returning it.
+        let mut default_attr = "#[serde(default)]".to_string();
+        let mut needs_default = f.omit_empty && f.rust_default;
+        let field_type = if is_string && config.borrowed_strings {
+            "Cow<'a, str>".to_string()
+        } else if is_string {
+            format!("Option<{}>", string_type)
+        } else {
+            rust_type.clone()
+        };
+        let mut default_body = None;
+        if let Some(value) = f.default_value.clone() {
+            needs_default = true;
+            default_body = default_literal(&field_type, &value)?;
+        } else if let Some(constant) = f.default_const.clone() {
+            needs_default = true;
+            let value = match context.constants.get(&constant) {
+                Some(path) => path.clone(),
+                None => {
+                    // Assume it is defined by hand next to the generated code.
+                    warn!("Default of {} references unknown constant {}", f.name, constant);
+                    format!("{}.into()", constant)
+                }
+            };
+            default_body = Some(if field_type.starts_with("Option<") {
+                format!("Some({})", value)
+            } else {
+                value
+            });
+        }
+        if let Some(body) = default_body {
+            let fn_name = format!(
+                "default_{}_{}",
+                struct_name.to_snake_case(),
+                member_name.trim_end_matches('_')
+            );
+            let mut function = codegen::Function::new(&fn_name);
+            function.ret(field_type.as_str());
+            function.line(body);
+            functions.push(function);
+            default_attr = format!("#[serde(default = \"{}\")]", fn_name);
+        }
+
+        if is_optional_time || made_optional {
+            needs_default = true;
+        }
+        if is_slice_alias {
+            // Like Go, an empty slice is left out.
+            needs_default = true;
+            rust_data
+                .annotations
+                .push("#[serde(skip_serializing_if = \"Vec::is_empty\")]".to_string());
+        }
+
+        if !f.embedded {
+            keys.insert(f.json_name.clone().unwrap_or_else(|| member_name.clone()));
+        }
+
+        let json_key = f.json_name.clone().unwrap_or_else(|| member_name.clone());
+        let bson_rename = f.bson_name.clone().filter(|name| config.bson_renames && *name != json_key);
+        let split_rename = f.rename_ser.is_some() || f.rename_de.is_some();
+        if split_rename {
+            // Takes precedence over the tags, serde only allows one rename.
+            rust_data.annotations.push(format!(
+                "#[serde(rename(serialize = \"{}\", deserialize = \"{}\"))]",
+                f.rename_ser.clone().unwrap_or_else(|| json_key.clone()),
+                f.rename_de.clone().unwrap_or_else(|| json_key.clone())
+            ));
+        } else if let Some(rename) = f.json_name.clone() {
+            if rename != member_name && bson_rename.is_some() {
+                // serde only allows a single rename.
+                rust_data.annotations.push(format!(
+                    "#[cfg_attr(not(feature = \"bson\"), serde(rename = \"{}\"))]",
+                    rename
+                ));
+            } else if rename != member_name {
+                rust_data
+                    .annotations
+                    .push(format!("#[serde(rename = \"{}\")]", rename));
+            }
+        }
+        if let Some(rename) = bson_rename.filter(|_| !split_rename) {
+            rust_data.annotations.push(format!(
+                "#[cfg_attr(feature = \"bson\", serde(rename = \"{}\"))]",
+                rename
+            ));
+        }
+
+        if f.embedded {
+            embedded.push(base_type.clone());
+            rust_data
+                .annotations
+                .push("#[serde(flatten)]".to_string());
+            if f.omit_empty {
+                // An absent embedded block deserializes to `None`. Note that
+                // serde can't tell a flattened block is absent if all of its
+                // fields are optional, so it will be `Some` with every field
+                // unset instead. Deserialization errors inside the block are
+                // also swallowed and produce `None`.
+                rust_data.annotations.push(default_attr.clone());
+                needs_default = false;
+            }
+        }
+
+        // Modules used with `#[serde(with = "...")]`, each with an `option`
+        // submodule for optional fields.
+        let mut with_module: Option<String> = None;
+
+        if let Some(layout) = f.layout.clone() {
+            if is_time {
+                // Without `emit_layout_modules` the module is expected to be
+                // in scope where the code is used.
+                let module = layout_module_name(&layout);
+                let format = go_layout_to_chrono(&layout);
+                if config.emit_layout_modules {
+                    raw_items.push(render_layout_module(&module, &format));
+                }
+                with_module = Some(module);
+                f.comments.push(format!("Serialized with the `{}` chrono format.", format));
+            } else {
+                warn!("Ignoring layout on non-time field: {}", f.name);
+            }
+        }
+
+        let display_from_str = config.use_serde_with && f.string_encoded && (is_bool || is_number);
+        if display_from_str {
+            // Go quotes these with the `,string` tag option.
+            serde_as = true;
+            libraries.insert("serde_with::DisplayFromStr".to_string());
+            let as_type = if rust_type.starts_with("Option<") {
+                "Option<DisplayFromStr>"
+            } else {
+                "DisplayFromStr"
+            };
+            rust_data
+                .annotations
+                .push(format!("#[serde_as(as = \"{}\")]", as_type));
+        }
+
+        let custom_helpers = config.use_custom_serde && !display_from_str;
+        if is_bool && config.tolerant_bool && custom_helpers && !rust_type.starts_with("Option<") {
+            // AWS sometimes sends booleans as strings or numbers.
+            libraries.insert("custom_serde::*".to_string());
+            rust_data
+                .annotations
+                .push("#[serde(deserialize_with = \"deserialize_lambda_bool\")]".to_string());
+        }
+
+        if let Some(helper) = integer_helper {
+            if config.tolerant_numbers && custom_helpers && !rust_type.starts_with("Option<") {
+                // AWS sometimes sends integers as strings or floats.
+                libraries.insert("custom_serde::*".to_string());
+                rust_data
+                    .annotations
+                    .push(format!("#[serde(deserialize_with = \"{}\")]", helper));
+            }
+        }
+
+        if is_big_int && config.use_custom_serde {
+            // Go encodes `big.Int` as a bare number that may not fit in `u64`.
+            libraries.insert("custom_serde::*".to_string());
+            with_module = Some("string_or_number".to_string());
+        }
+
+        if is_code_points && config.use_custom_serde {
+            libraries.insert("custom_serde::*".to_string());
+            with_module = Some("code_points".to_string());
+        }
+
+        if let Some(value) = sql_null.filter(|_| !config.sql_nulls_as_option) {
+            let module = format!("sql_null_{}", value.to_snake_case());
+            let value_type = sql_null_value_type(&format!("Null{}", value)).expect("known sql null type");
+            raw_items.push(render_sql_null_module(&module, &value, value_type));
+            rust_data
+                .annotations
+                .push(format!("#[serde(with = \"{}\")]", module));
+            rust_data.annotations.push("#[serde(default)]".to_string());
+        }
+
+        if let Some((timestamp, unit)) = epoch_timestamp.filter(|_| config.remote_timestamp_shims) {
+            if rust_type == timestamp {
+                libraries.insert("chrono::DateTime".to_string());
+                libraries.insert("chrono::Utc".to_string());
+                libraries.insert("custom_serde::*".to_string());
+                raw_items.push(render_remote_timestamp_shim(timestamp, unit));
+                rust_data
+                    .annotations
+                    .push(format!("#[serde(with = \"{}Def\")]", timestamp));
+            } else {
+                // serde can't apply a remote derive through an `Option`.
+                warn!("Not using a remote shim for optional timestamp {}", f.name);
+            }
+        }
+
+        if let Some(module) = with_module {
+            if f.omit_empty {
+                rust_data
+                    .annotations
+                    .push(format!("#[serde(with = \"{}::option\")]", module));
+                rust_data.annotations.push(default_attr.clone());
+                needs_default = false;
+            } else {
+                rust_data
+                    .annotations
+                    .push(format!("#[serde(with = \"{}\")]", module));
+            }
+        }
+
+        // Plain strings become an `Option` too, but Go always writes them.
+        if config.skip_serializing_none && rust_type.starts_with("Option<") {
+            rust_data
+                .annotations
+                .push("#[serde(skip_serializing_if = \"Option::is_none\")]".to_string());
+        }
+
+        let mut field_defs = vec![];
+
+        // Behavior overrides for specific types.
+        if is_string && config.borrowed_strings {
+            // Borrowing bypasses `deserialize_lambda_string`, so a missing
+            // field is empty and a `null` one fails to deserialize.
+            libraries.insert("std::borrow::Cow".to_string());
+            let mut borrowed_string = Field::new(&member_name, "Cow<'a, str>");
+            borrowed_string.annotation(vec!["#[serde(borrow)]", &default_attr]);
+            field_defs.push(borrowed_string);
+        } else if is_string {
+            // Go converts null strings to "" and sometimes is wrong about
+            // json string fields that can be `null`. We treat all `String`
+            // fields as `Option<String>` and convert `""` to `None`.
+            let mut string_as_option = Field::new(&member_name, &field_type);
+            if config.use_custom_serde {
+                libraries.insert("custom_serde::*".to_string());
+                let helper = if config.strings_as_boxed_str {
+                    "deserialize_lambda_boxed_str"
+                } else {
+                    "deserialize_lambda_string"
+                };
+                let annotation = format!("#[serde(deserialize_with = \"{}\")]", helper);
+                string_as_option.annotation(vec![&annotation, &default_attr]);
+            } else {
+                // Without the helper only a missing field maps to `None`.
+                string_as_option.annotation(vec![&default_attr]);
+            }
+            field_defs.push(string_as_option);
+        } else if MAP_RE.is_match(&rust_type) {
+            // We default to an empty map even if the field is `null`.
+            let mut map_as_empty = Field::new(&member_name, &rust_type);
+            if config.use_custom_serde {
+                libraries.insert("custom_serde::*".to_string());
+                map_as_empty.annotation(vec![
+                    "#[serde(deserialize_with = \"deserialize_lambda_map\")]",
+                    &default_attr,
+                ]);
+            } else {
+                // Without the helper a `null` map fails to deserialize.
+                map_as_empty.annotation(vec![&default_attr]);
+            }
+            if config.skip_serializing_none && f.omit_empty {
+                let mut annotations = map_as_empty.get_annotation();
+                annotations.push(format!(
+                    "#[serde(skip_serializing_if = \"{}::is_empty\")]",
+                    config.map_type.name()
+                ));
+                map_as_empty.annotation(annotations.iter().map(String::as_str).collect());
+            }
+            field_defs.push(map_as_empty);
+        } else {
+            let mut field = Field::new(&member_name, &rust_type);
+            if needs_default {
+                field.annotation(vec![&default_attr]);
+            }
+            field_defs = vec![field];
+        }
+
+        for mut field in field_defs {
+            if let Some(vis) = config.visibility.keyword() {
+                field.vis(vis);
+            }
+
+            if !f.comments.is_empty() {
+                field.doc(&f.comments.join("\n"));
+            }
+
+            if !rust_data.annotations.is_empty() {
+                let mut all_annotations: Vec<String> = field.get_annotation();
+                let mut new_annotations: Vec<String> = rust_data.annotations.clone();
+                all_annotations.append(&mut new_annotations);
+                field.annotation(all_annotations.iter().map(String::as_str).collect());
+            }
+
+            rust_struct.push_field(field);
+        }
+        if config.generate_validate {
+            if let Some(ref constraints) = f.validate {
+                validations.extend(validation_checks(&member_name, &field_type, constraints));
+            }
+        }
+        field_types.push(rust_type);
+        debug_fields.push((member_name, f.redact));
+    }
+
+    // An override may have replaced the only field using a generic.
+    let is_used = |generic: &str| {
+        field_types
+            .iter()
+            .any(|t| t.split(|c: char| !c.is_alphanumeric() && c != '_').any(|word| word == generic))
+    };
+    for generic in field_generics.into_iter().filter(|g| is_used(&g.value)) {
+        match generic.default {
+            None => {
+                struct_generics.push(generic.value.clone());
+            }
+            Some(default) => {
+                struct_generics.push(format!("{}={}", generic.value, default));
+            }
+        }
+
+        for bound in generic.bounds {
+            generic_bounds.push((generic.value.clone(), bound.clone()));
+            rust_struct.bound(&generic.value, bound);
+        }
+    }
+    if struct_generics.is_empty() {
+        // Only needed for the bounds.
+        libraries.remove("serde::de::DeserializeOwned");
+        libraries.remove("serde::ser::Serialize");
+    }
+
+    // Goes last so the map only sees keys no other flattened field claims.
+    if config.capture_extra {
+        libraries.insert("std::collections::HashMap".to_string());
+        libraries.insert("serde_json::Value".to_string());
+        let mut extra = Field::new("extra", "HashMap<String, Value>");
+        if let Some(vis) = config.visibility.keyword() {
+            extra.vis(vis);
+        }
+        extra.annotation(vec!["#[serde(flatten)]"]);
+        rust_struct.push_field(extra);
+        debug_fields.push(("extra".to_string(), false));
+    }
+
+    let mut impl_generics: Vec<String> = Vec::new();
+    if context.borrowed.contains(&struct_name.to_camel_case()) {
+        rust_struct.generic("'a");
+        impl_generics.push("'a".to_string());
+    }
+    for generic in struct_generics {
+        rust_struct.generic(&generic);
+        impl_generics.push(generic.split('=').next().unwrap_or("").to_string());
+    }
+
+    let mut impls: Vec<codegen::Impl> = Vec::new();
+    if redacted {
+        libraries.insert("std::fmt".to_string());
+        impls.push(redacted_debug_impl(
+            &struct_name.to_camel_case(),
+            &impl_generics,
+            &generic_bounds,
+            &debug_fields,
+        ));
+    }
+    if !base64_fields.is_empty() {
+        impls.push(base64_accessors_impl(
+            &struct_name.to_camel_case(),
+            &impl_generics,
+            &generic_bounds,
+            &base64_fields,
+            config.visibility,
+        ));
+    }
+
+    if config.generate_validate {
+        raw_items.push(render_validation_error(config.visibility));
+        impls.push(validate_impl(
+            &struct_name.to_camel_case(),
+            &impl_generics,
+            &generic_bounds,
+            &validations,
+            config.visibility,
+        ));
+    }
+
+    let mut attributes: Vec<String> = Vec::new();
+    let mut pre_derive_attributes: Vec<String> = Vec::new();
+    if serde_as {
+        libraries.insert("serde_with::serde_as".to_string());
+        pre_derive_attributes.push("#[serde_as]".to_string());
+    }
+    if let Some(rename) = config.struct_renames.get(&struct_name) {
+        attributes.push(format!("#[serde(rename = \"{}\")]", rename));
+    }
+
+    if let Some(ref hook) = config.struct_hook {
+        (hook.0)(&mut rust_struct);
+    }
+
+    Ok(ParsedStruct {
+        name: struct_name.to_camel_case(),
+        go_name: struct_name,
+        keys,
+        embedded,
+        rust_struct,
+        libraries,
+        functions,
+        raw_items,
+        attributes,
+        pre_derive_attributes,
+        impls,
+        nested,
+    })
+}
+
+// An impl block for a generated struct, with its generics and bounds.
+fn struct_impl(name: &str, generics: &[String], bounds: &[(String, String)]) -> codegen::Impl {
+    let mut imp = codegen::Impl::new(name);
+    for generic in generics {
+        imp.generic(generic);
+        imp.target_generic(generic.as_str());
+    }
+    for (generic, bound) in bounds {
+        imp.bound(generic, bound.as_str());
+    }
+    imp
+}
+
+fn render_validation_error(vis: Visibility) -> String {
+    format!(
+        r#"/// A field that fails a constraint of its `validate` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+{vis}struct ValidationError {{
+    {vis}field: &'static str,
+    {vis}constraint: &'static str,
+}}"#,
+        vis = vis.prefix()
+    )
+}
+
+// A `validate` method running the checks from `validation_checks`.
+fn validate_impl(
+    name: &str,
+    generics: &[String],
+    bounds: &[(String, String)],
+    checks: &[String],
+    vis: Visibility,
+) -> codegen::Impl {
+    let mut imp = struct_impl(name, generics, bounds);
+    let function = imp.new_fn("validate");
+    if let Some(vis) = vis.keyword() {
+        function.vis(vis);
+    }
+    function.arg_ref_self().ret("Result<(), ValidationError>");
+    for check in checks {
+        function.line(check);
+    }
+    function.line("Ok(())");
+    imp
+}
+
+// Checks a field of type `rust_type` against the `required`, `min=N` and
+// `max=N` constraints, on the length of strings and the value of numbers.
+// Other constraints are ignored.
+fn validation_checks(field: &str, rust_type: &str, constraints: &str) -> Vec<String> {
+    let (inner, optional) = match rust_type.strip_prefix("Option<").and_then(|t| t.strip_suffix('>')) {
+        Some(inner) => (inner, true),
+        None => (rust_type, false),
+    };
+    let is_string = matches!(inner, "String" | "Box<str>" | "Cow<'a, str>");
+    let is_float = inner == "f64";
+    let is_signed = matches!(inner, "i64" | "i32");
+    let is_unsigned = matches!(inner, "u64" | "u32" | "u8");
+    let key = field.trim_start_matches("r#");
+
+    let mut checks = Vec::new();
+    for constraint in constraints.split(',').map(str::trim) {
+        let error = format!(
+            "return Err(ValidationError {{ field: \"{}\", constraint: \"{}\" }});",
+            key, constraint
+        );
+        if constraint == "required" {
+            let condition = if optional {
+                format!("self.{}.is_none()", field)
+            } else if is_string {
+                format!("self.{}.is_empty()", field)
+            } else if is_float {
+                format!("self.{} == 0.0", field)
+            } else if is_signed || is_unsigned {
+                format!("self.{} == 0", field)
+            } else {
+                continue;
+            };
+            checks.push(format!("if {} {{\n    {}\n}}", condition, error));
+            continue;
+        }
+
+        let (op, bound) = match (constraint.strip_prefix("min="), constraint.strip_prefix("max=")) {
+            (Some(bound), _) => ("<", bound),
+            (_, Some(bound)) => (">", bound),
+            _ => continue,
+        };
+        let value = if optional { "value".to_string() } else { format!("self.{}", field) };
+        let condition = if is_string && bound.parse::<usize>().is_ok() {
+            format!("{}.chars().count() {} {}", value, op, bound)
+        } else if is_float && bound.parse::<f64>().is_ok() {
+            format!("{} {} {:?}", value, op, bound.parse::<f64>().expect("float bound"))
+        } else if (is_signed && bound.parse::<i64>().is_ok()) || (is_unsigned && bound.parse::<u64>().is_ok()) {
+            format!("{} {} {}", value, op, bound)
+        } else {
+            continue;
+        };
+        if optional {
+            let binding = if is_string { "ref value" } else { "value" };
+            checks.push(format!(
+                "if let Some({}) = self.{} {{\n    if {} {{\n        {}\n    }}\n}}",
+                binding, field, condition, error
+            ));
+        } else {
+            checks.push(format!("if {} {{\n    {}\n}}", condition, error));
+        }
+    }
+    checks
+}
+
+// Accessors for the bytes of `Base64Data` fields, from `(field, optional)`
+// pairs.
+fn base64_accessors_impl(
+    name: &str,
+    generics: &[String],
+    bounds: &[(String, String)],
+    fields: &[(String, bool)],
+    vis: Visibility,
+) -> codegen::Impl {
+    let mut imp = struct_impl(name, generics, bounds);
+    for &(ref field, optional) in fields {
+        let function = imp.new_fn(&format!("{}_bytes", field.trim_start_matches("r#").trim_end_matches('_')));
+        if let Some(vis) = vis.keyword() {
+            function.vis(vis);
+        }
+        function.arg_ref_self();
+        if optional {
+            function
+                .ret("Option<&[u8]>")
+                .line(format!("self.{}.as_ref().map(|data| data.0.as_slice())", field));
+        } else {
+            function.ret("&[u8]").line(format!("&self.{}.0", field));
+        }
+    }
+    imp
+}
+
+// Like the derived `Debug`, but hiding the values of redacted fields.
+fn redacted_debug_impl(
+    name: &str,
+    generics: &[String],
+    bounds: &[(String, String)],
+    fields: &[(String, bool)],
+) -> codegen::Impl {
+    let mut imp = struct_impl(name, generics, bounds);
+    imp.impl_trait("fmt::Debug");
+    for generic in generics.iter().filter(|g| !g.starts_with('\'')) {
+        imp.bound(generic, "fmt::Debug");
+    }
+
+    let mut body = format!("f.debug_struct(\"{}\")", name);
+    for &(ref field, redact) in fields {
+        let field_name = field.trim_start_matches("r#");
+        if redact {
+            body.push_str(&format!("\n    .field(\"{}\", &format_args!(\"[redacted]\"))", field_name));
+        } else {
+            body.push_str(&format!("\n    .field(\"{}\", &self.{})", field_name, field));
+        }
+    }
+    body.push_str("\n    .finish()");
+
+    imp.new_fn("fmt")
+        .arg_ref_self()
+        .arg("f", "&mut fmt::Formatter")
+        .ret("fmt::Result")
+        .line(body);
+    imp
+}
+
+// Best-effort check for keys claimed by more than one flattened type. Only
+// types defined in the same file are known.
+fn overlapping_keys(
+    embedded: &[String],
+    struct_keys: &HashMap<String, HashSet<String>>,
+) -> Vec<String> {
+    let mut seen: HashSet<&String> = HashSet::new();
+    let mut overlaps: Vec<String> = Vec::new();
+    for ty in embedded {
+        if let Some(keys) = struct_keys.get(ty) {
+            for key in keys {
+                if !seen.insert(key) && !overlaps.contains(key) {
+                    overlaps.push(key.clone());
+                }
+            }
+        }
+    }
+    overlaps.sort();
+    overlaps
+}
+
+// Renders a Go `default:"..."` tag value as a Rust expression of the field's
+// type. Returns `None` when the value is the type's `Default`.
+fn default_literal(rust_type: &str, value: &str) -> Result<Option<String>, CodegenError> {
+    let (inner, optional) = if rust_type.starts_with("Option<") && rust_type.ends_with('>') {
+        (&rust_type[7..rust_type.len() - 1], true)
+    } else {
+        (rust_type, false)
+    };
+
+    let invalid = || {
+        CodegenError::Validation(format!(
+            "invalid default value for {}: {:?}",
+            rust_type, value
+        ))
+    };
+
+    let (literal, is_zero) = match inner {
+        "i64" => {
+            let v: i64 = value.parse().map_err(|_| invalid())?;
+            (v.to_string(), v == 0)
+        }
+        "u64" | "u8" => {
+            let v: u64 = value.parse().map_err(|_| invalid())?;
+            (v.to_string(), v == 0)
+        }
+        "i32" => {
+            let v: i32 = value.parse().map_err(|_| invalid())?;
+            (v.to_string(), v == 0)
+        }
+        "u32" => {
+            let v: u32 = value.parse().map_err(|_| invalid())?;
+            (v.to_string(), v == 0)
+        }
+        "f64" => {
+            let v: f64 = value.parse().map_err(|_| invalid())?;
+            (format!("{:?}", v), v == 0.0)
+        }
+        "bool" => {
+            let v: bool = value.parse().map_err(|_| invalid())?;
+            (v.to_string(), !v)
+        }
+        "String" => (format!("{:?}.to_string()", value), value.is_empty()),
+        "Box<str>" => (format!("{:?}.into()", value), value.is_empty()),
+        "Cow<'a, str>" => (format!("Cow::Borrowed({:?})", value), value.is_empty()),
+        _ => return Err(invalid()),
+    };
+
+    if optional {
+        Ok(Some(format!("Some({})", literal)))
+    } else if is_zero {
+        Ok(None)
+    } else {
+        Ok(Some(literal))
+    }
+}
+
+fn parse_struct_preamble(pairs: Pairs<Rule>) -> Result<String, CodegenError> {
+    debug!("Parsing struct preamble");
+    let mut name: Option<String> = None;
+
+    for pair in pairs {
+        let span = pair.clone().into_span();
+        match pair.as_rule() {
+            Rule::struct_name => {
+                name = Some(span.as_str().to_string());
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    Ok(name.expect("structs always have a name"))
+}
+
+fn parse_struct_fields(pairs: Pairs<Rule>, config: &CodegenConfig) -> Result<Vec<FieldDef>, CodegenError> {
+    debug!("Parsing struct fields");
+
+    let mut fields: Vec<FieldDef> = Vec::new();
+
+    for pair in pairs {
+        match pair.as_rule() {
+            Rule::struct_field => {
+                // A `context.Context` is only used in-process and never serialized.
+                fields.extend(
+                    parse_struct_field(pair.into_inner(), config)?
+                        .into_iter()
+                        .filter(|f| !matches!(f.go_type, GoType::ContextType)),
+                );
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    Ok(fields)
+}
+
+// Returns a field for each of the names declared with the type.
+fn parse_struct_field(pairs: Pairs<Rule>, config: &CodegenConfig) -> Result<Vec<FieldDef>, CodegenError> {
+    debug!("Parsing struct field");
+    let mut names: Vec<String> = vec![];
+    let mut json: Option<JsonMapping> = None;
+    let mut go_type: Option<GoType> = None;
+    let mut comments: Vec<String> = vec![];
+    let mut is_pointer = false;
+    let mut embedded = false;
+
+    for pair in pairs {
+        debug!("{:?}", pair);
+        let span = pair.clone().into_span();
+        match pair.as_rule() {
+            Rule::json_mapping => json = Some(parse_json_mapping(pair.into_inner())?),
+            Rule::doc_comment
+                if is_empty_block_comment(span.as_str()) || is_go_directive(span.as_str()) => {}
+            Rule::doc_comment => comments.push(parse_comment(span.as_str())),
+            Rule::struct_field_decl => {
+                for pair in pair.into_inner() {
+                    let span = pair.clone().into_span();
+                    match pair.as_rule() {
+                        Rule::ident => names.push(mangle(span.as_str())),
+                        Rule::pointer => is_pointer = true,
+                        Rule::struct_field_type => {
+                            go_type = Some(parse_go_type(pair.into_inner(), config, 0)?)
+                        }
+                        Rule::struct_embedded_field => {
+                            info!("struct_embedded_field found: {:?}", pair);
+                            let value = pair.clone().into_span().as_str();
+                            names.push(mangle(value));
+                            go_type = Some(parse_go_type(pair.into_inner(), config, 0)?);
+                            embedded = true;
+                        },
+                        rule @ _ => panic!("invalid Rule found in struct_field_decl: {:?}", rule),
+                    }
+                }
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    // Only the name of a `bson` tag matters, its options are ignored.
+    let bson_name = json.as_ref().and_then(|j| {
+        j.other_tags
+            .iter()
+            .find(|tag| tag.0 == "bson")
+            .and_then(|tag| tag.1.split(',').next())
+            .filter(|name| !name.is_empty() && *name != "-")
+            .map(|name| name.to_string())
+    });
+
+    // A tag like `json:",omitempty"` keeps the default name.
+    let json_name = if let Some(j) = json.clone() {
+        j.name
+    } else {
+        None
+    };
+
+    let mut omit_empty = if let Some(j) = json.clone() {
+        // We omit empty (aka use an Option) if the JSON says so.
+        j.omit_empty
+    } else {
+        // By default we don't omit empty.
+        false
+    };
+
+    if !config.time_omitempty_as_option && matches!(go_type, Some(GoType::TimeType)) {
+        // `encoding/json` doesn't consider any `time.Time` empty.
+        omit_empty = false;
+    }
+
+    if is_pointer {
+        // If given a pointer, it can be `nil` and essentially empty.
+        omit_empty = true
+    }
+
+    // Parse inline comments after json definition.
+    if let Some(j) = json.clone() {
+        if !j.comments.is_empty() && !comments.is_empty() {
+            // Append inline comments with a blank comment line before them.
+            comments.push("".to_string());
+        }
+        comments.extend(j.comments);
+
+        // Constraints and examples are only documented, they don't change
+        // the type.
+        let mut example = None;
+        for (key, value) in j.other_tags {
+            if key == "validate" {
+                comments.push(format!("Validation: {}", value));
+            } else if key == "example" {
+                example = Some(value);
+            }
+        }
+        if let Some(example) = example {
+            if !comments.is_empty() {
+                comments.push("".to_string());
+            }
+            comments.push("# Example".to_string());
+            comments.push("".to_string());
+            comments.push(format!("`{}`", example));
+        }
+    };
+
+    lazy_static! {
+        static ref LAYOUT_RE: Regex = Regex::new(r"^layout:\s*(\S.*)$").expect("regex to compile");
+    }
+
+    let layout = comments
+        .iter()
+        .filter_map(|c| LAYOUT_RE.captures(c))
+        .map(|caps| caps[1].trim().to_string())
+        .next();
+
+    // `// rust:default` is an instruction for us rather than documentation.
+    let rust_default = comments.iter().any(|c| c == "rust:default");
+    comments.retain(|c| c != "rust:default");
+
+    let redact = comments.iter().any(|c| c == "rust:redact");
+    comments.retain(|c| c != "rust:redact");
+
+    let flatten_prefix = comments
+        .iter()
+        .filter_map(|c| c.strip_prefix("rust:flatten_prefix "))
+        .map(|prefix| prefix.trim().to_string())
+        .next();
+    comments.retain(|c| !c.starts_with("rust:flatten_prefix "));
+
+    // Both renames may share a line, like `// rust:rename_ser A // rust:rename_de B`.
+    let mut comments: Vec<String> = comments
+        .into_iter()
+        .flat_map(|c| {
+            if c.starts_with("rust:rename_") {
+                c.split("//").map(|part| part.trim().to_string()).collect()
+            } else {
+                vec![c]
+            }
+        })
+        .collect();
+
+    let rename_ser = comments
+        .iter()
+        .filter_map(|c| c.strip_prefix("rust:rename_ser "))
+        .map(|key| key.trim().to_string())
+        .next();
+    comments.retain(|c| !c.starts_with("rust:rename_ser "));
+
+    let rename_de = comments
+        .iter()
+        .filter_map(|c| c.strip_prefix("rust:rename_de "))
+        .map(|key| key.trim().to_string())
+        .next();
+    comments.retain(|c| !c.starts_with("rust:rename_de "));
+
+    let oneof = comments
+        .iter()
+        .filter_map(|c| c.strip_prefix("rust:oneof "))
+        .flat_map(|types| types.split_whitespace().map(|t| t.to_string()))
+        .collect();
+    comments.retain(|c| !c.starts_with("rust:oneof "));
+
+    let default_const = comments
+        .iter()
+        .filter_map(|c| c.strip_prefix("rust:default_const "))
+        .map(|name| name.trim().to_string())
+        .next();
+    comments.retain(|c| !c.starts_with("rust:default_const "));
+
+    let string_encoded = json.as_ref().is_some_and(|j| j.string_encoded);
+    let validate = json.as_ref().and_then(|j| {
+        j.other_tags
+            .iter()
+            .find(|tag| tag.0 == "validate")
+            .map(|tag| tag.1.clone())
+    });
+
+    let field = FieldDef {
+        name: names[0].clone(),
+        json_name,
+        comments,
+        omit_empty,
+        go_type: go_type.expect("fields have types"),
+        embedded,
+        layout,
+        default_value: json.and_then(|j| j.default_value),
+        rust_default,
+        default_const,
+        bson_name,
+        redact,
+        flatten_prefix,
+        rename_ser,
+        rename_de,
+        oneof,
+        validate,
+        pointer: is_pointer,
+        string_encoded,
+    };
+
+    Ok(names
+        .into_iter()
+        .map(|name| FieldDef {
+            name,
+            ..field.clone()
+        })
+        .collect())
+}
+
+// Converts a Go reference time layout (`Mon Jan 2 15:04:05 MST 2006`) into
+// a chrono format string.
+fn go_layout_to_chrono(layout: &str) -> String {
+    // Longer tokens first so e.g. `2006` wins over `2`.
+    const TOKENS: &[(&str, &str)] = &[
+        ("January", "%B"),
+        ("Monday", "%A"),
+        (".000000000", "%.9f"),
+        (".999999999", "%.f"),
+        (".000000", "%.6f"),
+        (".999999", "%.f"),
+        ("Z07:00", "%:z"),
+        ("-07:00", "%:z"),
+        (".000", "%.3f"),
+        (".999", "%.f"),
+        ("2006", "%Y"),
+        ("Z0700", "%z"),
+        ("-0700", "%z"),
+        ("Jan", "%b"),
+        ("Mon", "%a"),
+        ("MST", "%Z"),
+        ("002", "%j"),
+        ("_2", "%e"),
+        ("01", "%m"),
+        ("02", "%d"),
+        ("03", "%I"),
+        ("04", "%M"),
+        ("05", "%S"),
+        ("06", "%y"),
+        ("15", "%H"),
+        ("PM", "%p"),
+        ("pm", "%P"),
+        ("1", "%-m"),
+        ("2", "%-d"),
+        ("3", "%-I"),
+        ("4", "%-M"),
+        ("5", "%-S"),
+        ("%", "%%"),
+    ];
+
+    let mut out = String::new();
+    let mut rest = layout;
+    'outer: while !rest.is_empty() {
+        for &(go, chrono) in TOKENS {
+            if rest.starts_with(go) {
+                out.push_str(chrono);
+                rest = &rest[go.len()..];
+                continue 'outer;
+            }
+        }
+        let ch = rest.chars().next().expect("non-empty");
+        out.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    out
+}
+
+fn layout_module_name(layout: &str) -> String {
+    let sanitized: String = layout
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    format!("layout_{}", sanitized)
+}
+
+#[derive(Debug, Clone)]
+struct JsonMapping {
+    name: Option<String>,
+    comments: Vec<String>,
+    omit_empty: bool,
+    string_encoded: bool,
+    default_value: Option<String>,
+    // Tags other than `json` and `default`, in source order.
+    other_tags: Vec<(String, String)>,
+}
+
+fn parse_json_mapping(pairs: Pairs<Rule>) -> Result<JsonMapping, CodegenError> {
+    debug!("Parsing json mapping");
+    let mut name: Option<String> = None;
+    let mut comments: Vec<String> = Vec::new();
+    let mut omit_empty = false;
+    let mut string_encoded = false;
+    let mut default_value: Option<String> = None;
+    let mut other_tags: Vec<(String, String)> = Vec::new();
+
+    for pair in pairs {
+        debug!("{:?}", pair);
+        let span = pair.clone().into_span();
+        match pair.as_rule() {
+            Rule::json_name => name = Some(span.as_str().to_string()),
+            Rule::any_comment => comments.push(parse_comment(span.as_str())),
+            Rule::omit_empty => omit_empty = true,
+            Rule::string_option => string_encoded = true,
+            Rule::default_value => default_value = Some(span.as_str().to_string()),
+            Rule::other_tag => {
+                let mut key = String::new();
+                let mut value = String::new();
+                for pair in pair.into_inner() {
+                    let span = pair.clone().into_span();
+                    match pair.as_rule() {
+                        Rule::tag_key => key = span.as_str().to_string(),
+                        Rule::tag_value => value = span.as_str().to_string(),
+                        _ => unreachable!(),
+                    }
+                }
+                other_tags.push((key, value));
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    Ok(JsonMapping {
+        name,
+        comments,
+        omit_empty,
+        string_encoded,
+        default_value,
+        other_tags,
+    })
+}
+
+#[derive(Debug, Clone)]
+enum GoType {
+    StringType,
+    IntType,
+    UnsignedIntType,
+    // Bare `int` and `uint`, sized by `CodegenConfig::platform_int_bits`.
+    PlatformIntType,
+    PlatformUnsignedIntType,
+    FloatType,
+    BoolType,
+    ByteType,
+    RuneType,
+    // A `[]rune` from `CodegenConfig::rune_slice_as_string`.
+    CodePointsType,
+    UserDefined(String),
+    ArrayType(Box<GoType>),
+    FixedArrayType(Box<GoType>, usize),
+    MapType(Box<GoType>, Box<GoType>),
+    InterfaceType,
+    EmptyStructType,
+    // Replaced by a generated struct before translation.
+    AnonymousStruct(Vec<FieldDef>),
+    PointerType(Box<GoType>),
+    TimeType,
+    TimestampMillisecondsType,
+    TimestampSecondsType,
+    JsonRawType,
+    // A package type from `CodegenConfig::package_type_mappings`.
+    MappedType(String),
+    BigIntType,
+    BigFloatType,
+    // A `database/sql` null wrapper, by the name of its value field.
+    SqlNullType(String),
+    UuidType,
+    // Dropped when it's a struct field.
+    ContextType,
+    // A package type from `CodegenConfig::unknown_package_as_value`.
+    UnknownPackageType,
+}
+
+struct RustType {
+    annotations: Vec<String>,
+    libraries: HashSet<String>,
+    value: String,
+    generics: Vec<RustGeneric>,
+}
+
+#[derive(Clone)]
+struct RustGeneric {
+    value: String,
+    default: Option<String>,
+    bounds: Vec<String>,
+}
+
+// The grammar recurses once per slice, map and pointer, so deeply nested
+// types have to be rejected before parsing or they overflow the stack. Runs
+// of `[`, `map[` and `*` are counted up to the end of each type expression,
+// ignoring comments, strings and tags.
+fn check_source_nesting(source: &str, config: &CodegenConfig) -> Result<(), CodegenError> {
+    let mut depth = 0;
+    let mut chars = source.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' | '`' => {
+                depth = 0;
+                for (_, end) in chars.by_ref() {
+                    if end == c {
+                        break;
+                    }
+                }
+            }
+            '/' if source[i..].starts_with("//") => {
+                depth = 0;
+                for (_, end) in chars.by_ref() {
+                    if end == '\n' {
+                        break;
+                    }
+                }
+            }
+            '[' | '*' => {
+                depth += 1;
+                check_nesting_depth(depth, config)?;
+            }
+            // `map[` is counted by its bracket.
+            c if c.is_whitespace() || "{}(),;=".contains(c) => depth = 0,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Lets serde handle a field holding an array of more than 32 elements.
+const BIG_ARRAY_ANNOTATION: &str = "#[serde(with = \"BigArray\")]";
+
+fn check_nesting_depth(depth: usize, config: &CodegenConfig) -> Result<(), CodegenError> {
+    if depth > config.max_nesting_depth {
+        return Err(CodegenError::Validation(format!(
+            "type is nested deeper than the maximum depth of {}",
+            config.max_nesting_depth
+        )));
+    }
+    Ok(())
+}
+
+fn parse_go_type(pairs: Pairs<Rule>, config: &CodegenConfig, depth: usize) -> Result<GoType, CodegenError> {
+    debug!("Parsing go type");
+    check_nesting_depth(depth, config)?;
+    let mut go_type: Option<GoType> = None;
+
+    for pair in pairs {
+        debug!("{:?}", pair);
+        let value = pair.clone().into_span().as_str();
+        go_type = match pair.as_rule() {
+            Rule::array => Some(parse_go_type_array(pair.into_inner(), config, depth + 1)?),
+            Rule::fixed_array => Some(parse_go_type_fixed_array(pair.into_inner(), config, depth + 1)?),
+            Rule::primitive => Some(parse_go_type_primitive(value)?),
+            Rule::ident => Some(parse_go_ident(value)?),
+            Rule::package_ident => Some(parse_go_package_ident(value, config)?),
+            Rule::map => Some(parse_go_type_map(pair.into_inner(), config, depth + 1)?),
+            Rule::interface => Some(parse_go_type_interface(value)?),
+            Rule::empty_struct => Some(GoType::EmptyStructType),
+            Rule::anonymous_struct => {
+                let fields = pair.into_inner().next().expect("anonymous struct fields");
+                Some(GoType::AnonymousStruct(parse_struct_fields(fields.into_inner(), config)?))
+            }
+            Rule::pointer_type => Some(parse_go_type_pointer(pair.into_inner(), config, depth + 1)?),
+            _ => unimplemented!("{}\n{}", value, pair),
+        };
+    }
+
+    Ok(go_type.expect("parsing go type"))
+}
+
+fn parse_go_type_array(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+    depth: usize,
+) -> Result<GoType, CodegenError> {
+    debug!("Parsing go array");
+    check_nesting_depth(depth, config)?;
+    let mut go_type: Option<GoType> = None;
+
+    for pair in pairs {
+        debug!("{:?}", pair);
+        let value = pair.clone().into_span().as_str();
+        go_type = match pair.as_rule() {
+            Rule::primitive => match parse_go_type_primitive(value)? {
+                // Usually meant as text, though Go marshals it as numbers.
+                GoType::RuneType if config.rune_slice_as_string => Some(GoType::CodePointsType),
+                t => Some(GoType::ArrayType(Box::new(t))),
+            },
+            Rule::empty_struct => Some(GoType::ArrayType(Box::new(GoType::EmptyStructType))),
+            Rule::ident => Some(GoType::ArrayType(Box::new(GoType::UserDefined(
+                value.to_string(),
+            )))),
+            Rule::package_ident => Some(GoType::ArrayType(Box::new(parse_go_package_ident(value, config)?))),
+            Rule::map => Some(GoType::ArrayType(Box::new(parse_go_type_map(
+                pair.into_inner(),
+                config,
+                depth + 1,
+            )?))),
+            Rule::array => Some(GoType::ArrayType(Box::new(parse_go_type_array(
+                pair.into_inner(),
+                config,
+                depth + 1,
+            )?))),
+            Rule::fixed_array => Some(GoType::ArrayType(Box::new(parse_go_type_fixed_array(
+                pair.into_inner(),
+                config,
+                depth + 1,
+            )?))),
+            // Elements are plain options, `nil` and `""` stay distinct.
+            Rule::pointer_type => Some(GoType::ArrayType(Box::new(parse_go_type_pointer(
+                pair.into_inner(),
+                config,
+                depth + 1,
+            )?))),
+            _ => unimplemented!(),
+        };
+    }
+
+    Ok(go_type.expect("parsing go array"))
+}
+
+fn parse_go_type_fixed_array(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+    depth: usize,
+) -> Result<GoType, CodegenError> {
+    debug!("Parsing go fixed array");
+    check_nesting_depth(depth, config)?;
+    let mut len: Option<usize> = None;
+    let mut element: Option<GoType> = None;
+
+    for pair in pairs {
+        debug!("{:?}", pair);
+        let value = pair.clone().into_span().as_str();
+        match pair.as_rule() {
+            Rule::array_len => {
+                len = Some(value.parse().map_err(|_| {
+                    CodegenError::Parse(format!("invalid array length: {}", value))
+                })?)
+            }
+            Rule::fixed_array_element => {
+                element = Some(parse_go_type(pair.into_inner(), config, depth + 1)?)
+            }
+            _ => unimplemented!(),
+        };
+    }
+
+    Ok(GoType::FixedArrayType(
+        Box::new(element.expect("parsing fixed array element")),
+        len.expect("parsing fixed array length"),
+    ))
+}
+
+fn parse_go_type_map(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+    depth: usize,
+) -> Result<GoType, CodegenError> {
+    debug!("Parsing go map");
+    check_nesting_depth(depth, config)?;
+    let mut key_type: Option<GoType> = None;
+    let mut value_type: Option<GoType> = None;
+
+    for pair in pairs {
+        debug!("{:?}", pair);
+        let value = pair.clone().into_span().as_str();
+        match pair.as_rule() {
+            Rule::key_type => key_type = Some(parse_go_type_primitive(value)?),
+            Rule::value_type => {
+                value_type = Some(parse_go_type(pair.into_inner(), config, depth + 1)?)
+            }
+            _ => unimplemented!(),
+        };
+    }
+
+    Ok(GoType::MapType(
+        Box::new(key_type.expect("parsing map key")),
+        Box::new(value_type.expect("parsing map value")),
+    ))
+}
+
+fn parse_go_type_interface(_t: &str) -> Result<GoType, CodegenError> {
+    // For now we don't parse.
+    Ok(GoType::InterfaceType)
+}
+
+fn parse_go_type_pointer(
+    pairs: Pairs<Rule>,
+    config: &CodegenConfig,
+    depth: usize,
+) -> Result<GoType, CodegenError> {
+    debug!("Parsing go pointer");
+    check_nesting_depth(depth, config)?;
+    let mut pointed_at = None;
+    for pair in pairs {
+        debug!("{:?}", pair);
+        match pair.as_rule() {
+            Rule::pointer => (),
+            Rule::value_type => {
+                pointed_at = Some(parse_go_type(pair.into_inner(), config, depth + 1)?)
+            }
+            _ => unimplemented!(),
+        };
+    }
+    Ok(GoType::PointerType(Box::new(pointed_at.expect("something pointed at"))))
+}
+
+fn parse_go_type_primitive(t: &str) -> Result<GoType, CodegenError> {
+    match t {
+        "string" => Ok(GoType::StringType),
+        "int" => Ok(GoType::PlatformIntType),
+        "uint" => Ok(GoType::PlatformUnsignedIntType),
+        "int32" | "int64" => Ok(GoType::IntType),
+        "uint32" | "uint64" => Ok(GoType::UnsignedIntType),
+        "float" | "float32" | "float64" => Ok(GoType::FloatType),
+        "bool" => Ok(GoType::BoolType),
+        "byte" => Ok(GoType::ByteType),
+        "rune" => Ok(GoType::RuneType),
+        _ => unimplemented!("missing go type primitive"),
+    }
+}
+
+fn parse_go_ident(t: &str) -> Result<GoType, CodegenError> {
+    match t {
+        "MilliSecondsEpochTime" => Ok(GoType::TimestampMillisecondsType),
+        "SecondsEpochTime" => Ok(GoType::TimestampSecondsType),
+        _ => Ok(GoType::UserDefined(t.to_string())),
+    }
+}
+
+fn parse_go_package_ident(t: &str, config: &CodegenConfig) -> Result<GoType, CodegenError> {
+    if config.package_type_mappings.contains_key(t) {
+        return Ok(GoType::MappedType(t.to_string()));
+    }
+    let (package, name) = match t.find('.') {
+        Some(i) => (&t[..i], &t[i + 1..]),
+        None => return Err(CodegenError::UnsupportedType(t.to_string())),
+    };
+    let path = match config.import_paths.get(package) {
+        Some(path) => path.as_str(),
+        None => standard_package_path(package),
+    };
+    match (path, name) {
+        ("time", "Time") => Ok(GoType::TimeType),
+        ("encoding/json", "RawMessage") => Ok(GoType::JsonRawType),
+        ("math/big", "Int") => Ok(GoType::BigIntType),
+        ("math/big", "Float") => Ok(GoType::BigFloatType),
+        ("github.com/google/uuid", "UUID") => Ok(GoType::UuidType),
+        ("context", "Context") => Ok(GoType::ContextType),
+        ("database/sql", _) if sql_null_value_type(name).is_some() => {
+            Ok(GoType::SqlNullType(name["Null".len()..].to_string()))
+        }
+        _ if config.unknown_package_as_value => {
+            warn!("Translating unknown package type {} as a JSON value", t);
+            Ok(GoType::UnknownPackageType)
+        }
+        _ => Err(CodegenError::UnsupportedType(t.to_string())),
+    }
+}
+
+// Import path assumed for a package the source doesn't import.
+fn standard_package_path(package: &str) -> &str {
+    match package {
+        "json" => "encoding/json",
+        "big" => "math/big",
+        "sql" => "database/sql",
+        "uuid" => "github.com/google/uuid",
+        _ => package,
+    }
+}
+
+// Rust type of the value of a `database/sql` null wrapper like `NullString`.
+fn sql_null_value_type(name: &str) -> Option<&'static str> {
+    match name {
+        "NullString" => Some("String"),
+        "NullInt64" | "NullInt32" | "NullInt16" => Some("i64"),
+        "NullByte" => Some("u8"),
+        "NullFloat64" => Some("f64"),
+        "NullBool" => Some("bool"),
+        _ => None,
+    }
+}
+
+fn field_member_name(f: &FieldDef, naming: FieldNaming) -> String {
+    if f.embedded {
+        return mangle(&f.name.to_snake_case());
+    }
+    match naming {
+        FieldNaming::SnakeCase => mangle(&f.name.to_snake_case()),
+        FieldNaming::CamelCase => mangle(&f.name.to_mixed_case()),
+        FieldNaming::Original => match f.json_name {
+            Some(ref json_name) if is_identifier(json_name) => mangle(json_name),
+            _ => mangle(&f.name.to_snake_case()),
+        },
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn mangle(s: &str) -> String {
+    // TODO: Add more keywords.
+    match s {
+        "ref" => "ref_".to_string(),
+        "type" => "type_".to_string(),
+        _ => s.to_string(),
+    }
+}
+
+fn make_rust_type_with_no_libraries(value: &str) -> RustType {
+    RustType {
+        annotations: vec![],
+        value: value.to_string(),
+        generics: vec![],
+        libraries: HashSet::new(),
+    }
+}
+
+fn translate_go_type_to_rust_type(
+    go_type: GoType,
+    generic_counter: Option<&mut usize>,
+    config: &CodegenConfig,
+    depth: usize,
+) -> Result<RustType, CodegenError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("translate_go_type_to_rust_type", go_type = ?go_type).entered();
+    check_nesting_depth(depth, config)?;
+    let rust_type = match &go_type {
+        GoType::StringType => make_rust_type_with_no_libraries("String"),
+        GoType::BoolType => make_rust_type_with_no_libraries("bool"),
+        GoType::ByteType => make_rust_type_with_no_libraries("u8"),
+        // Go encodes a rune as its code point, a number. A `char` would
+        // need a helper to read it, which can't reach nested runes.
+        GoType::RuneType => make_rust_type_with_no_libraries("i32"),
+        GoType::CodePointsType => make_rust_type_with_no_libraries("String"),
+        GoType::IntType => make_rust_type_with_no_libraries("i64"),
+        GoType::UnsignedIntType => make_rust_type_with_no_libraries("u64"),
+        GoType::PlatformIntType if config.platform_int_bits == 32 => make_rust_type_with_no_libraries("i32"),
+        GoType::PlatformUnsignedIntType if config.platform_int_bits == 32 => {
+            make_rust_type_with_no_libraries("u32")
+        }
+        GoType::PlatformIntType => make_rust_type_with_no_libraries("i64"),
+        GoType::PlatformUnsignedIntType => make_rust_type_with_no_libraries("u64"),
+        GoType::FloatType if config.ordered_floats => {
+            let mut libraries = HashSet::new();
+            libraries.insert("ordered_float::OrderedFloat".to_string());
+            RustType {
+                annotations: vec![],
+                value: "OrderedFloat<f64>".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+        GoType::FloatType => make_rust_type_with_no_libraries("f64"),
+        GoType::UserDefined(x) => make_rust_type_with_no_libraries(&x.to_camel_case()),
+        GoType::EmptyStructType => make_rust_type_with_no_libraries(EMPTY_STRUCT_NAME),
+        // Only direct struct fields can be anonymous structs.
+        GoType::AnonymousStruct(_) => {
+            return Err(CodegenError::UnsupportedType("anonymous struct".to_string()))
+        }
+        GoType::ContextType => {
+            return Err(CodegenError::UnsupportedType("context.Context".to_string()))
+        }
+        GoType::ArrayType(x) => {
+            let i = translate_go_type_to_rust_type(*x.clone(), generic_counter, config, depth + 1)?;
+            
+            if i.value == "u8" {
+                let mut libraries = i.libraries.clone();
+                libraries.insert(format!("{}::Base64Data", config.encodings_path));
+                // Handle []u8 special, as it is base64 encoded.
+                RustType {
+                    annotations: i.annotations,
+                    value: "Base64Data".to_string(),
+                    generics: i.generics,
+                    libraries: libraries,
+                }
+            } else {
+                RustType {
+                    annotations: i.annotations,
+                    value: format!("Vec<{}>", i.value),
+                    generics: i.generics,
+                    libraries: i.libraries,
+                }
+            }
+        },
+        GoType::PointerType(v) => {
+            let data = translate_go_type_to_rust_type(*v.clone(), generic_counter, config, depth + 1)?;
+            let libraries: HashSet<String> = data.libraries.iter().cloned().collect();
+            RustType {
+                annotations: data.annotations,
+                value: format!("Option<{}>", data.value),
+                generics: data.generics,
+                libraries,
+            }
+        },
+        GoType::FixedArrayType(x, len) => {
+            let mut i = translate_go_type_to_rust_type(*x.clone(), generic_counter, config, depth + 1)?;
+            // serde only implements its traits for arrays of up to 32 elements.
+            if *len > 32 {
+                // `BigArray` only applies to a field's own type.
+                if depth > 0 {
+                    return Err(CodegenError::UnsupportedType(format!(
+                        "array of {} elements inside another type",
+                        len
+                    )));
+                }
+                i.libraries.insert("serde_big_array::BigArray".to_string());
+                i.annotations.push(BIG_ARRAY_ANNOTATION.to_string());
+            }
+            RustType {
+                annotations: i.annotations,
+                value: format!("[{}; {}]", i.value, len),
+                generics: i.generics,
+                libraries: i.libraries,
+            }
+        }
+        GoType::MapType(k, v) => {
+            // TODO can we use a ref to the option to save this dance?
+            let mut generics = 0;
+
+            if let Some(ref generic_counter) = generic_counter {
+                generics = **generic_counter;
+            }
+
+            let key_data =
+                translate_go_type_to_rust_type(*k.clone(), Some(&mut generics), config, depth + 1)?;
+            let value_data =
+                translate_go_type_to_rust_type(*v.clone(), Some(&mut generics), config, depth + 1)?;
+
+            if let Some(mut generic_counter) = generic_counter {
+                *generic_counter = generics;
+            }
+
+            let mut annotations = Vec::new();
+            annotations.extend(key_data.annotations);
+            annotations.extend(value_data.annotations);
+
+            let mut generics = Vec::new();
+            generics.extend(key_data.generics);
+            generics.extend(value_data.generics);
+
+            let mut libraries = HashSet::new();
+            libraries.extend(key_data.libraries);
+            libraries.extend(value_data.libraries);
+            libraries.insert(config.map_type.path().to_string());
+
+            RustType {
+                value: format!("{}<{}, {}>", config.map_type.name(), key_data.value, value_data.value),
+                annotations,
+                generics,
+                libraries,
+            }
+        }
+        GoType::UnknownPackageType => {
+            let mut libraries = HashSet::new();
+            libraries.insert("serde_json::Value".to_string());
+
+            RustType {
+                annotations: vec![],
+                value: "Value".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+        GoType::MappedType(path) => {
+            let (value, libraries) = &config.package_type_mappings[path];
+            RustType {
+                annotations: vec![],
+                value: value.clone(),
+                generics: vec![],
+                libraries: libraries.clone(),
+            }
+        }
+        GoType::BigIntType if config.big_numbers => {
+            let mut libraries = HashSet::new();
+            libraries.insert("num_bigint::BigInt".to_string());
+
+            RustType {
+                annotations: vec![],
+                value: "BigInt".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+        GoType::BigFloatType if config.big_numbers => {
+            // Go encodes `big.Float` as a string, which is also what
+            // `bigdecimal` does with its `serde` feature.
+            let mut libraries = HashSet::new();
+            libraries.insert("bigdecimal::BigDecimal".to_string());
+
+            RustType {
+                annotations: vec![],
+                value: "BigDecimal".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+        GoType::BigIntType | GoType::BigFloatType => make_rust_type_with_no_libraries("String"),
+        GoType::UuidType if config.uuids => {
+            // Both encode it as a hyphenated string.
+            let mut libraries = HashSet::new();
+            libraries.insert("uuid::Uuid".to_string());
+
+            RustType {
+                annotations: vec![],
+                value: "Uuid".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+        GoType::UuidType => make_rust_type_with_no_libraries("String"),
+        GoType::SqlNullType(value) => {
+            let value_type = sql_null_value_type(&format!("Null{}", value)).expect("known sql null type");
+            make_rust_type_with_no_libraries(&format!("Option<{}>", value_type))
+        }
+        GoType::JsonRawType if config.raw_json_values => {
+            // Keeps the exact input text instead of reparsing it.
+            let mut libraries = HashSet::new();
+            libraries.insert("serde_json::value::RawValue".to_string());
+
+            RustType {
+                annotations: vec![],
+                value: "Box<RawValue>".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+        // For now we treat interfaces as a generic JSON value and make callers
+        // deal with it.
+        GoType::InterfaceType | GoType::JsonRawType => {
+            let mut libraries = HashSet::new();
+            libraries.insert("serde_json::Value".to_string());
+
+            match generic_counter {
+                Some(mut counter) if !config.interfaces_as_value => {
+                    *counter = *counter + 1;
+                    let next_generic = format!("T{}", counter);
+
+                    libraries.insert("serde::de::DeserializeOwned".to_string());
+                    libraries.insert("serde::ser::Serialize".to_string());
+
+                    RustType {
+                        annotations: vec!["#[serde(bound=\"\")]".to_string()],
+                        value: next_generic.clone(),
+                        generics: vec![RustGeneric {
+                            value: next_generic.clone(),
+                            default: Some("Value".to_string()),
+                            bounds: vec![
+                                "DeserializeOwned".to_string(),
+                                "Serialize".to_string(),
+                            ],
+                        }],
+                        libraries,
+                    }
+                }
+                _ => {
+                    RustType {
+                        annotations: vec![],
+                        value: "Value".to_string(),
+                        generics: vec![],
+                        libraries,
+                    }
+                }
+            }
+        }
+        GoType::TimestampSecondsType => {
+            let mut libraries = HashSet::new();
+            libraries.insert(format!("{}::SecondTimestamp", config.encodings_path));
+            RustType {
+                annotations: vec![],
+                value: "SecondTimestamp".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+        GoType::TimestampMillisecondsType => {
+            let mut libraries = HashSet::new();
+            libraries.insert(format!("{}::MillisecondTimestamp", config.encodings_path));
+
+            RustType {
+                annotations: vec![],
+                value: "MillisecondTimestamp".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+        GoType::TimeType => {
+            // No need for custom deserialization as Go's time.Time type
+            // deserializes to chrono's default format. Neat.
+            let mut libraries = HashSet::new();
             libraries.insert("chrono::DateTime".to_string());
             libraries.insert("chrono::Utc".to_string());
 
-            RustType {
-                annotations: vec![],
-                value: "DateTime<Utc>".to_string(),
-                generics: vec![],
-                libraries,
-            }
+            RustType {
+                annotations: vec![],
+                value: "DateTime<Utc>".to_string(),
+                generics: vec![],
+                libraries,
+            }
+        }
+    };
+
+    Ok(rust_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod primitives {
+        use super::*;
+
+        #[test]
+        fn test_parses_array() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "[]bool",
+                rule: Rule::array,
+                tokens: [
+                    array(0, 6, [
+                        primitive(2, 6, [
+                            boolean(2, 6),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "[]blah",
+                rule: Rule::array,
+                tokens: [
+                    array(0, 6, [
+                        ident(2, 6),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_map() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "map[string]interface{}",
+                rule: Rule::map,
+                tokens: [
+                    map(0, 22, [
+                        key_type(4, 10, [
+                            primitive(4, 10, [
+                                string(4, 10),
+                            ]),
+                        ]),
+                        value_type(11, 22, [
+                            interface(11, 22),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+    }
+
+    mod directives {
+        use super::*;
+
+        #[test]
+        fn test_parses_package_def() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "package foo",
+                rule: Rule::package_def,
+                tokens: [
+                    package_def(0, 11, [
+                        ident(8, 11),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_backtick_in_comment() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "// use `json` tags\ntype MyFoo struct {}",
+                rule: Rule::struct_def,
+                tokens: [
+                    struct_def(0, 39, [
+                        doc_comment(0, 19, [
+                            any_comment(0, 18),
+                        ]),
+                        struct_preamble(19, 36, [
+                            struct_name(24, 29, [
+                                ident(24, 29),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_multiple_tags() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"foo\" validate:\"required\"`",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 32, [
+                        json_name(7, 10),
+                        other_tag(12, 31, [
+                            tag_key(12, 20),
+                            tag_value(22, 30),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_json_and_xml_tags() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"name,omitempty\" xml:\"name\"`",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 34, [
+                        json_name(7, 11),
+                        omit_empty(11, 21),
+                        other_tag(23, 33, [
+                            tag_key(23, 26),
+                            tag_value(28, 32),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`xml:\"name\"`",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 12, [
+                        other_tag(1, 11, [
+                            tag_key(1, 4),
+                            tag_value(6, 10),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_go_generate() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "//go:generate stringer -type=Foo",
+                rule: Rule::go_generate,
+                tokens: [
+                    go_generate(0, 32, [
+                        go_generate_command(14, 32),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_collect_go_generate_directives() {
+            let path = std::env::temp_dir().join("go_to_rust_go_generate.go");
+            File::create(&path)
+                .and_then(|mut f| {
+                    f.write_all(
+                        b"package foo\n\n//go:generate stringer -type=Foo\n// not a directive\n//go:generate go run gen.go\n",
+                    )
+                })
+                .expect("writes fixture");
+
+            let commands = collect_go_generate_directives(&path).expect("reads directives");
+            assert_eq!(commands, vec!["stringer -type=Foo", "go run gen.go"]);
+        }
+
+        #[test]
+        fn test_parses_constraint_interface() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "type Number interface { ~int | ~float64 }",
+                rule: Rule::constraint_interface,
+                tokens: [
+                    constraint_interface(0, 41, [
+                        ident(5, 11),
+                        primitive(25, 28, [
+                            int(25, 28),
+                        ]),
+                        primitive(32, 39, [
+                            float(32, 39),
+                        ]),
+                    ]),
+                ]
+            };
+
+            let input = "type Number interface { ~int | ~float64 }\n\ntype Foo struct {}";
+            let (_, rust) = parse_go_string(input.to_string()).expect("parser parses");
+            assert!(!rust.to_string().contains("Number"));
+        }
+
+        #[test]
+        fn test_parses_struct_def() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "type MyFoo struct {}",
+                rule: Rule::struct_def,
+                tokens: [
+                    struct_def(0, 20, [
+                        struct_preamble(0, 17, [
+                            struct_name(5, 10, [
+                                ident(5, 10),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "type MyFoo struct { foo string }",
+                rule: Rule::struct_def,
+                tokens: [
+                    struct_def(0, 32, [
+                        struct_preamble(0, 17, [
+                            struct_name(5, 10, [
+                                ident(5, 10),
+                            ]),
+                        ]),
+                        struct_fields(20, 31, [
+                            struct_field(20, 31, [
+                                struct_field_decl(20, 30, [
+                                    ident(20, 23),
+                                    struct_field_type(24, 30, [
+                                        primitive(24, 30, [
+                                            string(24, 30)
+                                        ]),
+                                    ]),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "type MyFoo struct { T1 }",
+                rule: Rule::struct_def,
+                tokens: [
+                    struct_def(0, 24, [
+                        struct_preamble(0, 17, [
+                            struct_name(5, 10, [
+                                ident(5, 10),
+                            ]),
+                        ]),
+                        struct_fields(20, 23, [
+                            struct_field(20, 23, [
+                                struct_field_decl(20, 22, [
+                                    struct_embedded_field(20, 22, [
+                                        ident(20, 22),
+                                    ]),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "type MyFoo struct { *T1 }",
+                rule: Rule::struct_def,
+                tokens: [
+                    struct_def(0, 25, [
+                        struct_preamble(0, 17, [
+                            struct_name(5, 10, [
+                                ident(5, 10),
+                            ]),
+                        ]),
+                        struct_fields(20, 24, [
+                            struct_field(20, 24, [
+                                struct_field_decl(20, 23, [
+                                    pointer(20, 21),
+                                    struct_embedded_field(21, 23, [
+                                        ident(21, 23),
+                                    ]),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: r#"type MyFoo struct {
+                  foo string
+                  bar int
+                }"#,
+                rule: Rule::struct_def,
+                tokens: [
+                    struct_def(0, 92, [
+                        struct_preamble(0, 17, [
+                            struct_name(5, 10, [
+                                ident(5, 10),
+                            ]),
+                        ]),
+                        struct_fields(38, 74, [
+                            struct_field(38, 48, [
+                                struct_field_decl(38, 48, [
+                                    ident(38, 41),
+                                    struct_field_type(42, 48, [
+                                        primitive(42, 48, [
+                                            string(42, 48)
+                                        ]),
+                                    ]),
+                                ]),
+                            ]),
+                            struct_field(67, 74, [
+                                struct_field_decl(67, 74, [
+                                    ident(67, 70),
+                                    struct_field_type(71, 74, [
+                                        primitive(71, 74, [
+                                            int(71, 74)
+                                        ]),
+                                    ]),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_struct_field() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "EventVersion string",
+                rule: Rule::struct_field,
+                tokens: [
+                    struct_field(0, 19, [
+                        struct_field_decl(0, 19, [
+                            ident(0, 12),
+                            struct_field_type(13, 19, [
+                                primitive(13, 19, [
+                                    string(13, 19),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "EventVersion bool",
+                rule: Rule::struct_field,
+                tokens: [
+                    struct_field(0, 17, [
+                        struct_field_decl(0, 17, [
+                            ident(0, 12),
+                            struct_field_type(13, 17, [
+                                primitive(13, 17, [
+                                    boolean(13, 17),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "EventVersion *bool",
+                rule: Rule::struct_field,
+                tokens: [
+                    struct_field(0, 18, [
+                        struct_field_decl(0, 18, [
+                            ident(0, 12),
+                            pointer(13, 14),
+                            struct_field_type(14, 18, [
+                                primitive(14, 18, [
+                                    boolean(14, 18),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_struct_embedded_field() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "EventVersion",
+                rule: Rule::struct_field,
+                tokens: [
+                    struct_field(0, 12, [
+                        struct_field_decl(0, 12, [
+                            struct_embedded_field(0, 12, [
+                                ident(0, 12),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "*EventVersion",
+                rule: Rule::struct_field,
+                tokens: [
+                    struct_field(0, 13, [
+                        struct_field_decl(0, 13, [
+                            pointer(0, 1),
+                            struct_embedded_field(1, 13, [
+                                ident(1, 13),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "EventVersion MyType",
+                rule: Rule::struct_field,
+                tokens: [
+                    struct_field(0, 19, [
+                        struct_field_decl(0, 19, [
+                            ident(0, 12),
+                            struct_field_type(13, 19, [
+                                ident(13, 19),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_json_mapping() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"fooBar\"`",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 15, [
+                        json_name(7, 13),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"foo-x\"`",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 14, [
+                        json_name(7, 12),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"foo.x\"`",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 14, [
+                        json_name(7, 12),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"foo,omitempty\"`",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 22, [
+                        json_name(7, 10),
+                        omit_empty(10, 20),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"fooBar\"` // whatever",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 27, [
+                        json_name(7, 13),
+                        any_comment(16, 27),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_json_mapping_across_lines() {
+            // A comment on the line after the last field belongs to that field.
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"foo\"`\n\t// bar\n}",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 20, [
+                        json_name(7, 10),
+                        any_comment(14, 20),
+                    ]),
+                ]
+            };
+
+            // Otherwise it documents the next field.
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"foo\"`\n\t// bar\n\tBaz int",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 12, [
+                        json_name(7, 10),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "`json:\"foo\"\n\txml:\"bar\"`",
+                rule: Rule::json_mapping,
+                tokens: [
+                    json_mapping(0, 23, [
+                        json_name(7, 10),
+                        other_tag(13, 22, [
+                            tag_key(13, 16),
+                            tag_value(18, 21),
+                        ]),
+                    ]),
+                ]
+            };
+
+            let source = "type Foo struct {\n\tBar string `json:\"bar\"`\n\t// The bar.\n}";
+            let rendered = render_struct(source, &CodegenConfig::default()).expect("struct renders");
+            assert!(rendered.contains("    /// The bar.\n"), "{}", rendered);
+        }
+
+        #[test]
+        fn test_parses_empty_block_comment() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "/**/\n\tBar int",
+                rule: Rule::struct_field,
+                tokens: [
+                    struct_field(0, 13, [
+                        doc_comment(0, 5, [
+                            any_comment(0, 4),
+                        ]),
+                        struct_field_decl(6, 13, [
+                            ident(6, 9),
+                            struct_field_type(10, 13, [
+                                primitive(10, 13, [
+                                    int(10, 13),
+                                ]),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+
+            let source = "type Foo struct {\n\tFoo int `json:\"foo\"`\n\t/**/\n\tBar int `json:\"bar\"`\n\t/* */\n\tBaz int `json:\"baz\"`\n}";
+            let rendered = render_struct(source, &CodegenConfig::default()).expect("struct renders");
+            assert!(!rendered.contains("///"), "{}", rendered);
+        }
+
+        #[test]
+        fn test_skips_go_directives_on_fields() {
+            let source = "type Foo struct {\n\t// The bar.\n\t//go:nosplit\n\tBar int `json:\"bar\"`\n}";
+            let rendered = render_struct(source, &CodegenConfig::default()).expect("struct renders");
+            assert!(rendered.contains("    /// The bar.\n    pub bar: i64,"), "{}", rendered);
+            assert!(!rendered.contains("go:nosplit"), "{}", rendered);
+        }
+
+        #[test]
+        fn test_parses_unusual_json_tags() {
+            fn mapping(input: &str) -> JsonMapping {
+                let mut pairs = AwsGoEventsParser::parse(Rule::json_mapping, input).expect("tag parses");
+                parse_json_mapping(pairs.next().expect("json mapping").into_inner()).expect("mapping parses")
+            }
+
+            // A field literally named `-`.
+            let m = mapping("`json:\"-,omitempty\"`");
+            assert_eq!(m.name, Some("-".to_string()));
+            assert!(m.omit_empty);
+
+            let m = mapping("`json:\",,\"`");
+            assert_eq!(m.name, None);
+            assert!(!m.omit_empty);
+            assert!(m.other_tags.is_empty());
+
+            let m = mapping("`json:\"\"`");
+            assert_eq!(m.name, None);
+            assert!(!m.omit_empty);
+
+            let m = mapping("`json:\"id,string,omitempty\"`");
+            assert_eq!(m.name, Some("id".to_string()));
+            assert!(m.omit_empty);
+        }
+
+        #[test]
+        fn test_parses_fixed_array() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "[3]Coordinate",
+                rule: Rule::fixed_array,
+                tokens: [
+                    fixed_array(0, 13, [
+                        array_len(1, 2),
+                        fixed_array_element(3, 13, [
+                            ident(3, 13),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_empty_struct_field() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "Marker struct{}",
+                rule: Rule::struct_field,
+                tokens: [
+                    struct_field(0, 15, [
+                        struct_field_decl(0, 15, [
+                            ident(0, 6),
+                            struct_field_type(7, 15, [
+                                empty_struct(7, 15),
+                            ]),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_import() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "import \"foo\"",
+                rule: Rule::import,
+                tokens: [
+                    import(0, 12, [
+                        import_package(7, 12, [
+                            package_name(8, 11),
+                        ]),
+                    ]),
+                ]
+            };
+
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "import \"a/b\"",
+                rule: Rule::import,
+                tokens: [
+                    import(0, 12, [
+                        import_package(7, 12, [
+                            package_name(8, 11),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_aliased_import() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "import j \"encoding/json\"",
+                rule: Rule::import,
+                tokens: [
+                    import(0, 24, [
+                        import_alias(7, 8),
+                        import_package(9, 24, [
+                            package_name(10, 23),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+
+        #[test]
+        fn test_parses_mutiple_imports() {
+            parses_to! {
+                parser: AwsGoEventsParser,
+                input: "import (\n\"foo\"\n \"bar\"\n)",
+                rule: Rule::import_multiple,
+                tokens: [
+                    import_multiple(0, 23, [
+                        import_package(9, 14, [
+                            package_name(10, 13),
+                        ]),
+                        import_package(16, 21, [
+                            package_name(17, 20),
+                        ]),
+                    ]),
+                ]
+            };
+        }
+    }
+
+    mod errors {
+        use super::*;
+
+        #[test]
+        fn test_parse_error() {
+            match parse_go_string("type {".to_string()) {
+                Err(CodegenError::Parse(_)) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_unsupported_type_error() {
+            match parse_go_string("type Foo mylib.Thing".to_string()) {
+                Err(CodegenError::UnsupportedType(ref t)) if t == "mylib.Thing" => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_io_error() {
+            match parse_go_file(&PathBuf::from("does/not/exist.go")) {
+                Err(CodegenError::Io(_)) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_duplicate_structs() {
+            // Identical definitions are merged.
+            let input = "type MyFoo struct {\n\tBar int\n}\n\ntype MyFoo struct {\n\tBar int\n}";
+            let (_, rust) = parse_go_string(input.to_string()).expect("parser parses");
+            assert_eq!(rust.to_string().matches("pub struct MyFoo").count(), 1);
+
+            let input = "type MyFoo struct {\n\tBar int\n}\n\ntype MyFoo struct {\n\tBaz int\n}";
+            match parse_go_string(input.to_string()) {
+                Err(CodegenError::Validation(ref e)) => assert_eq!(
+                    e,
+                    "struct `MyFoo` is defined more than once with different contents"
+                ),
+                other => panic!("unexpected result: {:?}", other),
+            }
+
+            let input = "type MyFoo struct {\n\tBar int\n}\n\ntype my_foo struct {\n\tBar int\n}";
+            match parse_go_string(input.to_string()) {
+                Err(CodegenError::Validation(ref e)) => {
+                    assert_eq!(e, "structs `MyFoo` and `my_foo` both generate `MyFoo`")
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_validation_error() {
+            let config = CodegenConfig {
+                max_nesting_depth: 1,
+                ..Default::default()
+            };
+            match parse_go_string_with_config("type Foo [][][]string".to_string(), &config) {
+                Err(CodegenError::Validation(_)) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    mod spans {
+        use super::super::*;
+        use std::sync::{Arc, Mutex};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        // Records the name of every span created.
+        struct SpanNames(Arc<Mutex<Vec<String>>>);
+
+        impl Subscriber for SpanNames {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes) -> Id {
+                let mut names = self.0.lock().unwrap();
+                names.push(span.metadata().name().to_string());
+                Id::from_u64(names.len() as u64)
+            }
+            fn record(&self, _: &Id, _: &Record) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        #[test]
+        fn test_parse_stages_have_spans() {
+            let input = r#"type Count int
+
+type MyFoo struct {
+	Bar int `json:"bar"`
+}"#;
+
+            let names = Arc::new(Mutex::new(Vec::new()));
+            tracing::subscriber::with_default(SpanNames(names.clone()), || {
+                parse_go_string(input.to_string()).expect("input to parse");
+            });
+
+            let names = names.lock().unwrap();
+            for stage in &["parse_struct", "field", "parse_type_alias", "translate_go_type_to_rust_type"] {
+                assert!(names.iter().any(|n| n == stage), "no `{}` span", stage);
+            }
+        }
+    }
+
+    mod warnings {
+        use super::super::*;
+        use log::{Level, LevelFilter, Log, Metadata, Record};
+        use std::sync::{Mutex, Once};
+
+        lazy_static! {
+            static ref WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+        }
+
+        // Records every warning logged, from all tests.
+        struct Warnings;
+
+        impl Log for Warnings {
+            fn enabled(&self, metadata: &Metadata) -> bool {
+                metadata.level() <= Level::Warn
+            }
+            fn log(&self, record: &Record) {
+                if self.enabled(record.metadata()) {
+                    WARNINGS.lock().unwrap().push(record.args().to_string());
+                }
+            }
+            fn flush(&self) {}
+        }
+
+        static LOGGER: Warnings = Warnings;
+        static INIT: Once = Once::new();
+
+        fn install_logger() {
+            INIT.call_once(|| {
+                log::set_logger(&LOGGER).expect("no other logger");
+                log::set_max_level(LevelFilter::Warn);
+            });
+        }
+
+        #[test]
+        fn test_unknown_package_as_value() {
+            install_logger();
+            let input = r#"type MyFoo struct {
+	Thing somepkg.Thing `json:"thing"`
+}"#;
+
+            let config = CodegenConfig {
+                unknown_package_as_value: true,
+                ..Default::default()
+            };
+            let (_, rust) = parse_go_string_with_config(input.to_string(), &config).expect("parser parses");
+            assert_eq!(
+                rust.to_string(),
+                r#"use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub thing: Value,
+}"#
+            );
+            let warnings = WARNINGS.lock().unwrap();
+            assert!(warnings
+                .iter()
+                .any(|w| w == "Translating unknown package type somepkg.Thing as a JSON value"));
+
+            match parse_go_string(input.to_string()) {
+                Err(CodegenError::UnsupportedType(t)) => assert_eq!(t, "somepkg.Thing"),
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    mod translate {
+        use super::*;
+
+        fn translate(input: &str, config: &CodegenConfig) -> String {
+            let (_, rust) = parse_go_string_with_config(input.to_string(), config)
+                .expect("parser parses");
+            rust.to_string()
+        }
+
+        #[test]
+        fn test_reexport_package_aliases() {
+            let input = "type Raw json.RawMessage\n\ntype Time time.Time";
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use serde_json::Value;
+use chrono::{DateTime, Utc};
+
+pub type Raw = Value;
+
+pub type Time = DateTime<Utc>;"#
+            );
+
+            // Types with generic arguments can't be re-exported.
+            let config = CodegenConfig {
+                reexport_package_aliases: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use chrono::{DateTime, Utc};
+
+pub use serde_json::Value as Raw;
+
+pub type Time = DateTime<Utc>;"#
+            );
+        }
+
+        #[test]
+        fn test_backtick_in_comment() {
+            let input = r#"// use `json` tags
+type MyFoo struct {
+	// Bar is `quoted`
+	Bar string `json:"bar"` // not a `tag`
+}"#;
+
+            let output = translate(input, &CodegenConfig::default());
+            assert!(output.contains("/// use `json` tags\n"));
+            assert!(output.contains("    /// Bar is `quoted`\n    ///\n    /// not a `tag`\n"));
+        }
+
+        #[test]
+        fn test_embedded_omit_empty() {
+            let input = r#"type MyFoo struct {
+	Bar `json:",omitempty"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(flatten)]
+    #[serde(default)]
+    pub bar: Option<Bar>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_string_enums() {
+            let input = r#"type MyKind string
+
+const (
+	// Foo things
+	MyKindFooBar MyKind = "FOO_BAR"
+	MyKindBaz    MyKind = "BAZ" // Baz things
+)"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                "pub type MyKind = String;"
+            );
+
+            let config = CodegenConfig {
+                string_enums: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum MyKind {
+    /// Foo things
+    #[serde(rename = "FOO_BAR")]
+    FooBar,
+    /// Baz things
+    #[serde(rename = "BAZ")]
+    Baz,
+}"#
+            );
+
+            let config = CodegenConfig {
+                string_enums: true,
+                enum_rename_all: Some("SCREAMING_SNAKE_CASE".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MyKind {
+    /// Foo things
+    FooBar,
+    /// Baz things
+    Baz,
+}"#
+            );
+
+            // Values that don't follow the convention keep per-variant renames.
+            let config = CodegenConfig {
+                string_enums: true,
+                enum_rename_all: Some("kebab-case".to_string()),
+                ..Default::default()
+            };
+            assert!(translate(input, &config).contains("#[serde(rename = \"FOO_BAR\")]"));
+        }
+
+        #[test]
+        fn test_apply_rename_all() {
+            assert_eq!(apply_rename_all("camelCase", "FooBar"), Some("fooBar".to_string()));
+            assert_eq!(apply_rename_all("camelCase", ""), Some(String::new()));
+            assert_eq!(apply_rename_all("camelCase", "Ünit"), Some("ünit".to_string()));
+            assert_eq!(apply_rename_all("snake_case", ""), Some(String::new()));
+            assert_eq!(apply_rename_all("unknown", "FooBar"), None);
+        }
+
+        #[test]
+        fn test_map_of_pointers() {
+            let input = r#"type MyFoo struct {
+	Bars map[string]*Bar `json:"bars"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    pub bars: HashMap<String, Option<Bar>>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_encodings_path() {
+            let input = r#"type MyFoo struct {
+	Data []byte `json:"data"`
+}"#;
+
+            let config = CodegenConfig {
+                encodings_path: "crate::encodings".to_string(),
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use crate::encodings::Base64Data;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub data: Base64Data,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_time_layout() {
+            let input = r#"type MyFoo struct {
+	// layout: 2006-01-02
+	Day time.Time `json:"day"`
+	Stamp *time.Time `json:"stamp"` // layout: Jan _2 15:04:05.000
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    /// layout: 2006-01-02
+    /// Serialized with the `%Y-%m-%d` chrono format.
+    #[serde(with = "layout_2006_01_02")]
+    pub day: DateTime<Utc>,
+    /// layout: Jan _2 15:04:05.000
+    /// Serialized with the `%b %e %H:%M:%S%.3f` chrono format.
+    #[serde(with = "layout_jan__2_15_04_05_000::option")]
+    #[serde(default)]
+    pub stamp: Option<DateTime<Utc>>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_default_tag() {
+            let input = r#"type MyFoo struct {
+	Count int `json:"count" default:"10"`
+	Zero  int `json:"zero" default:"0"`
+	Name  string `json:"name" default:"foo"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default = "default_my_foo_count")]
+    pub count: i64,
+    #[serde(default)]
+    pub zero: i64,
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default = "default_my_foo_name")]
+    pub name: Option<String>,
+}
+
+fn default_my_foo_count() -> i64 {
+    10
+}
+
+fn default_my_foo_name() -> Option<String> {
+    Some("foo".to_string())
+}"#
+            );
+
+            match parse_go_string("type MyFoo struct {\n\tCount int `default:\"ten\"`\n}".to_string()) {
+                Err(CodegenError::Validation(_)) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_pointer_embedded() {
+            let input = r#"type MyFoo struct {
+	*Base
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(flatten)]
+    #[serde(default)]
+    pub base: Option<Base>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_multiple_embedded() {
+            let input = r#"type MyFoo struct {
+	Bar
+	*Baz
+	Qux string `json:"qux"`
+}"#;
+
+            let output = translate(input, &CodegenConfig::default());
+            assert!(output.contains(
+                r#"    #[serde(flatten)]
+    pub bar: Bar,
+    #[serde(flatten)]
+    #[serde(default)]
+    pub baz: Option<Baz>,
+"#
+            ));
+        }
+
+        #[test]
+        fn test_overlapping_keys() {
+            let mut struct_keys = HashMap::new();
+            struct_keys.insert(
+                "Bar".to_string(),
+                vec!["id".to_string(), "bar".to_string()].into_iter().collect(),
+            );
+            struct_keys.insert(
+                "Baz".to_string(),
+                vec!["id".to_string(), "baz".to_string()].into_iter().collect(),
+            );
+
+            let embedded = vec!["Bar".to_string(), "Baz".to_string(), "Unknown".to_string()];
+            assert_eq!(overlapping_keys(&embedded, &struct_keys), vec!["id".to_string()]);
+            assert!(overlapping_keys(&embedded[..1], &struct_keys).is_empty());
+        }
+
+        #[test]
+        fn test_validate_tag() {
+            let input = r#"type MyFoo struct {
+	// The count
+	Count int `json:"count,omitempty" validate:"required" xml:"count"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    /// The count
+    /// Validation: required
+    #[serde(default)]
+    pub count: Option<i64>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_borrowed_strings() {
+            let input = r#"type MyFoo struct {
+	Name string `json:"name"`
+	Note *string `json:"note"`
+	Bars []Bar `json:"bars"`
+}
+
+type Bar struct {
+	Id string `json:"id"`
+	Any interface{} `json:"any"`
+}
+
+type Baz struct {
+	Count int `json:"count"`
+}"#;
+
+            let config = CodegenConfig {
+                borrowed_strings: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use std::borrow::Cow;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo<'a> {
+    #[serde(borrow)]
+    #[serde(default)]
+    pub name: Cow<'a, str>,
+    #[serde(default)]
+    pub note: Option<String>,
+    pub bars: Vec<Bar<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Bar<'a, T1=Value>
+where T1: DeserializeOwned,
+      T1: Serialize,
+{
+    #[serde(borrow)]
+    #[serde(default)]
+    pub id: Cow<'a, str>,
+    #[serde(bound="")]
+    pub any: T1,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Baz {
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_raw_json_values() {
+            let input = r#"type MyFoo struct {
+	Payload json.RawMessage `json:"payload"`
+}
+
+type Outer struct {
+	Foos []MyFoo `json:"foos"`
+}
+
+type Other struct {
+	Count int `json:"count"`
+}"#;
+
+            let config = CodegenConfig {
+                raw_json_values: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use serde_json::value::RawValue;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub payload: Box<RawValue>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Outer {
+    pub foos: Vec<MyFoo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Other {
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_time_collection_aliases() {
+            assert_eq!(
+                translate("type Timestamps []time.Time", &CodegenConfig::default()),
+                r#"use chrono::{DateTime, Utc};
+
+pub type Timestamps = Vec<DateTime<Utc>>;"#
+            );
+
+            assert_eq!(
+                translate("type TimeMap map[string]time.Time", &CodegenConfig::default()),
+                r#"use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+pub type TimeMap = HashMap<String, DateTime<Utc>>;"#
+            );
+        }
+
+        #[test]
+        fn test_package_type_mappings() {
+            let input = r#"type MyFoo struct {
+	Id mylib.UUID `json:"id"`
+	Ids []mylib.UUID `json:"ids"`
+}"#;
+
+            let mut config = CodegenConfig::default();
+            config.package_type_mappings.insert(
+                "mylib.UUID".to_string(),
+                (
+                    "Uuid".to_string(),
+                    vec!["uuid::Uuid".to_string()].into_iter().collect(),
+                ),
+            );
+            assert_eq!(
+                translate(input, &config),
+                r#"use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub id: Uuid,
+    pub ids: Vec<Uuid>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_big_numbers() {
+            let input = r#"type MyFoo struct {
+	Amount *big.Int `json:"amount"`
+	Total big.Int `json:"total"`
+	Rate big.Float `json:"rate"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(with = "string_or_number::option")]
+    #[serde(default)]
+    pub amount: Option<String>,
+    #[serde(with = "string_or_number")]
+    pub total: String,
+    pub rate: String,
+}"#
+            );
+
+            let config = CodegenConfig {
+                big_numbers: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use bigdecimal::BigDecimal;
+use custom_serde::*;
+use num_bigint::BigInt;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(with = "string_or_number::option")]
+    #[serde(default)]
+    pub amount: Option<BigInt>,
+    #[serde(with = "string_or_number")]
+    pub total: BigInt,
+    pub rate: BigDecimal,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_multi_line_field_comment() {
+            let input = r#"type MyFoo struct {
+	// Attributes of the item:
+	// - name: the item name
+	// - size: the item size
+	Attributes map[string]string `json:"attributes"`
+}"#;
+
+            let output = translate(input, &CodegenConfig::default());
+            assert!(output.contains(
+                r#"    /// Attributes of the item:
+    /// - name: the item name
+    /// - size: the item size
+    #[serde(deserialize_with"#
+            ));
+        }
+
+        #[test]
+        fn test_rust_default_directive() {
+            let input = r#"type MyFoo struct {
+	// The name
+	// rust:default
+	Name string `json:"name,omitempty"`
+	Count *int `json:"count"` // rust:default
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    /// The name
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_default_const_directive() {
+            let input = r#"type Status string
+
+const (
+	StatusActive Status = "active"
+	StatusInactive Status = "inactive"
+)
+
+type MyFoo struct {
+	// rust:default_const StatusActive
+	Status Status `json:"status"`
+}"#;
+
+            let config = CodegenConfig {
+                string_enums: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Status {
+    #[serde(rename = "active")]
+    Active,
+    #[serde(rename = "inactive")]
+    Inactive,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default = "default_my_foo_status")]
+    pub status: Status,
+}
+
+fn default_my_foo_status() -> Status {
+    Status::Active
+}"#
+            );
+        }
+
+        #[test]
+        fn test_untagged_interfaces() {
+            let input = r#"type MyFoo struct {
+	Payload interface{} `json:"payload"`
+	Other interface{} `json:"other"`
+}"#;
+
+            let mut config = CodegenConfig::default();
+            config.untagged_interfaces.insert(
+                FieldPath::new("MyFoo", "Payload"),
+                vec!["Bar".to_string(), "Vec<Baz>".to_string()],
+            );
+            assert_eq!(
+                translate(input, &config),
+                r#"use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo<T1=Value>
+where T1: DeserializeOwned,
+      T1: Serialize,
+{
+    pub payload: MyFooPayload,
+    #[serde(bound="")]
+    pub other: T1,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MyFooPayload {
+    Bar(Bar),
+    VecBaz(Vec<Baz>),
+}"#
+            );
+        }
+
+        #[test]
+        fn test_oneof_directive() {
+            let input = r#"type MyFoo struct {
+	// The payload.
+	// rust:oneof Bar Baz qux
+	Payload interface{} `json:"payload"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    /// The payload.
+    pub payload: MyFooPayload,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MyFooPayload {
+    Bar(Bar),
+    Baz(Baz),
+    Qux(Qux),
+}"#
+            );
+        }
+
+        #[test]
+        fn test_overridden_interface_has_no_generic() {
+            let input = r#"type MyFoo struct {
+	Payload interface{} `json:"payload"`
+	Count int `json:"count"`
+}"#;
+
+            let mut config = CodegenConfig::default();
+            config
+                .untagged_interfaces
+                .insert(FieldPath::new("MyFoo", "Payload"), vec!["Bar".to_string()]);
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub payload: MyFooPayload,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MyFooPayload {
+    Bar(Bar),
+}"#
+            );
+        }
+
+        #[test]
+        fn test_import_aliases() {
+            let input = r#"import (
+	j "encoding/json"
+	json "github.com/example/json"
+)
+
+type MyFoo struct {
+	Raw j.RawMessage `json:"raw"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo<T1=Value>
+where T1: DeserializeOwned,
+      T1: Serialize,
+{
+    #[serde(bound="")]
+    pub raw: T1,
+}"#
+            );
+
+            let input = r#"import json "github.com/example/json"
+
+type MyFoo struct {
+	Raw json.RawMessage `json:"raw"`
+}"#;
+            match parse_go_string(input.to_string()) {
+                Err(CodegenError::UnsupportedType(t)) => assert_eq!(t, "json.RawMessage"),
+                other => panic!("expected an unsupported type, got {:?}", other.map(|_| ())),
+            }
+        }
+
+        #[test]
+        fn test_capture_extra() {
+            let input = r#"type MyFoo struct {
+	Bar
+	Name string `json:"name"`
+}"#;
+
+            let config = CodegenConfig {
+                capture_extra: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(flatten)]
+    pub bar: Bar,
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_optional_time() {
+            let input = r#"type MyFoo struct {
+	Start *time.Time `json:"start"`
+	End time.Time `json:"end,omitempty"`
+}"#;
+
+            let config = CodegenConfig {
+                skip_serializing_none: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<DateTime<Utc>>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<DateTime<Utc>>,
+}"#
+            );
+
+            let config = CodegenConfig {
+                time_omitempty_as_option: false,
+                ..config
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<DateTime<Utc>>,
+    pub end: DateTime<Utc>,
+}"#
+            );
+
+            // Go always writes strings without `omitempty`, empty or not.
+            let input = r#"type MyFoo struct {
+	Name string `json:"name"`
+	Nickname string `json:"nickname,omitempty"`
+}"#;
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_fixed_arrays() {
+            let input = r#"type MyFoo struct {
+	Corners [3]Coordinate `json:"corners"`
+	Digest [16]byte `json:"digest"`
+	Samples [64]float64 `json:"samples"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use serde_big_array::BigArray;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub corners: [Coordinate; 3],
+    pub digest: [u8; 16],
+    #[serde(with = "BigArray")]
+    pub samples: [f64; 64],
+}"#
+            );
+        }
+
+        #[test]
+        fn test_merge() {
+            let (_, first) = parse_go_string(
+                r#"type MyFoo struct {
+	At time.Time `json:"at"`
+}"#
+                .to_string(),
+            )
+            .expect("parser parses");
+            let (_, second) = parse_go_string(
+                r#"type MyBar struct {
+	At time.Time `json:"at"`
+	Tags map[string]string `json:"tags"`
+}"#
+                .to_string(),
+            )
+            .expect("parser parses");
+
+            assert_eq!(
+                merge(vec![first, second]).to_string(),
+                r#"use chrono::{DateTime, Utc};
+use custom_serde::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBar {
+    pub at: DateTime<Utc>,
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_merge_shared_helpers() {
+            let (_, first) = parse_go_string(
+                r#"type MyFoo struct {
+	Marker struct{} `json:"marker"`
+}"#
+                .to_string(),
+            )
+            .expect("parser parses");
+            let (_, second) = parse_go_string(
+                r#"type MyBar struct {
+	Markers []struct{} `json:"markers"`
+}"#
+                .to_string(),
+            )
+            .expect("parser parses");
+
+            assert_eq!(
+                merge(vec![first, second]).to_string(),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub marker: Empty,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Empty {}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBar {
+    pub markers: Vec<Empty>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_emit_into_scope() {
+            let mut scope = Scope::new();
+            scope.import("std::collections", "HashMap");
+            scope.raw("pub type Registry = HashMap<String, MyFoo>;");
+
+            let config = CodegenConfig::default();
+            emit_into_scope(
+                r#"type MyFoo struct {
+	At time.Time `json:"at"`
+	Marker struct{} `json:"marker"`
+}"#,
+                &mut scope,
+                &config,
+            )
+            .expect("parser parses");
+            emit_into_scope(
+                r#"type MyBar struct {
+	Tags map[string]string `json:"tags"`
+	Marker struct{} `json:"marker"`
+}"#,
+                &mut scope,
+                &config,
+            )
+            .expect("parser parses");
+
+            assert_eq!(
+                scope.to_string(),
+                r#"use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use custom_serde::*;
+
+pub type Registry = HashMap<String, MyFoo>;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub at: DateTime<Utc>,
+    pub marker: Empty,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Empty {}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBar {
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    pub marker: Empty,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_required_imports() {
+            let input = r#"type MyFoo struct {
+	At time.Time `json:"at"`
+	Tags map[string]string `json:"tags"`
+}"#;
+
+            let expected = vec![
+                "chrono::DateTime".to_string(),
+                "chrono::Utc".to_string(),
+                "custom_serde::*".to_string(),
+                "std::collections::HashMap".to_string(),
+            ];
+            let (_, rust) = parse_go_string(input.to_string()).expect("parser parses");
+            assert_eq!(required_imports(&rust), expected);
+
+            // Still known once the imports are rendered into a raw item.
+            let config = CodegenConfig {
+                allow_unused_imports: true,
+                ..Default::default()
+            };
+            let (_, rust) = parse_go_string_with_config(input.to_string(), &config).expect("parser parses");
+            assert_eq!(required_imports(&rust), expected);
+        }
+
+        #[test]
+        fn test_empty_struct() {
+            let input = r#"type MyFoo struct {
+	Marker struct{} `json:"marker"`
+	Markers []struct{} `json:"markers"`
+}
+
+type MyBar struct {
+	Marker struct{} `json:"marker"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub marker: Empty,
+    pub markers: Vec<Empty>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Empty {}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBar {
+    pub marker: Empty,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_interfaces_as_value() {
+            let input = r#"type MyFoo struct {
+	First interface{} `json:"first"`
+	Second interface{} `json:"second"`
+}"#;
+
+            let config = CodegenConfig {
+                interfaces_as_value: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub first: Value,
+    pub second: Value,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_tolerant_bool() {
+            let input = r#"type MyFoo struct {
+	Enabled bool `json:"enabled"`
+	Flag *bool `json:"flag"`
+}"#;
+
+            let config = CodegenConfig {
+                tolerant_bool: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_bool")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub flag: Option<bool>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_generate_dispatch_enum() {
+            let variants = vec![
+                ("S3".to_string(), "S3Event".to_string()),
+                ("Sns".to_string(), "SnsEvent".to_string()),
+                ("Sqs".to_string(), "SqsEvent".to_string()),
+            ];
+            assert_eq!(
+                generate_dispatch_enum("Event", &variants, "source").to_string(),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "source")]
+pub enum Event {
+    S3(S3Event),
+    Sns(SnsEvent),
+    Sqs(SqsEvent),
+}"#
+            );
+
+            // The tag is written as a string literal.
+            let variants = vec![("S3".to_string(), "S3Event".to_string())];
+            assert_eq!(
+                generate_dispatch_enum("Event", &variants, r#"event "source"\"#).to_string(),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "event \"source\"\\")]
+pub enum Event {
+    S3(S3Event),
+}"#
+            );
+        }
+
+        #[test]
+        fn test_enum_kind() {
+            let variants = vec![
+                ("S3".to_string(), "S3Event".to_string()),
+                ("Sns".to_string(), "SnsEvent".to_string()),
+            ];
+            let config = CodegenConfig {
+                emit_enum_kind: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                generate_dispatch_enum_with_config("Event", &variants, "source", &config).to_string(),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "source")]
+pub enum Event {
+    S3(S3Event),
+    Sns(SnsEvent),
+}
+
+impl Event {
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Event::S3(_) => "S3",
+            Event::Sns(_) => "Sns",
+        }
+    }
+}"#
+            );
+
+            let input = r#"type Status string
+
+const (
+	StatusActive Status = "active"
+	StatusInactive Status = "inactive"
+)"#;
+            let config = CodegenConfig {
+                string_enums: true,
+                ..config
+            };
+            assert!(translate(input, &config).ends_with(
+                r#"impl Status {
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Status::Active => "active",
+            Status::Inactive => "inactive",
+        }
+    }
+}"#
+            ));
+        }
+
+        #[test]
+        fn test_impl_from_str() {
+            let input = r#"type Status string
+
+const (
+	StatusActive Status = "active"
+	StatusInactive Status = "inactive"
+)"#;
+            let config = CodegenConfig {
+                string_enums: true,
+                impl_from_str: true,
+                ..Default::default()
+            };
+            assert!(translate(input, &config).ends_with(
+                r#"impl ::std::str::FromStr for Status {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Status::Active),
+            "inactive" => Ok(Status::Inactive),
+            _ => Err(format!("unknown Status: {}", s)),
+        }
+    }
+}
+
+impl<'a> ::std::convert::TryFrom<&'a str> for Status {
+    type Error = String;
+
+    fn try_from(s: &'a str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}"#
+            ));
+        }
+
+        #[test]
+        fn test_skip_empty_maps() {
+            let input = r#"type MyFoo struct {
+	Tags map[string]string `json:"tags,omitempty"`
+	Labels map[string]string `json:"labels"`
+}"#;
+
+            let config = CodegenConfig {
+                skip_serializing_none: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub tags: HashMap<String, String>,
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_rune_slices() {
+            let input = r#"type MyFoo struct {
+	Initial rune `json:"initial"`
+	Letters []rune `json:"letters"`
+	Nickname []rune `json:"nickname,omitempty"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub initial: i32,
+    pub letters: Vec<i32>,
+    #[serde(default)]
+    pub nickname: Option<Vec<i32>>,
+}"#
+            );
+
+            let config = CodegenConfig {
+                rune_slice_as_string: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub initial: i32,
+    #[serde(with = "code_points")]
+    pub letters: String,
+    #[serde(with = "code_points::option")]
+    #[serde(default)]
+    pub nickname: Option<String>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_render_struct() {
+            let input = r#"type MyFoo struct {
+	Name string `json:"name"`
+	At time.Time `json:"at"`
+}"#;
+
+            let rendered = render_struct(input, &CodegenConfig::default()).expect("struct renders");
+            assert!(rendered.starts_with("use chrono::{DateTime, Utc};\nuse custom_serde::*;\n"));
+            assert!(rendered.contains("pub struct MyFoo {"));
+            assert!(rendered.contains("    pub name: Option<String>,"));
+            assert!(rendered.contains("    pub at: DateTime<Utc>,"));
+
+            let input = format!("{}\n\ntype MyBar struct {{\n}}", input);
+            match render_struct(&input, &CodegenConfig::default()) {
+                Err(CodegenError::Parse(_)) => {}
+                other => panic!("expected a parse error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_omitempty_pointer_to_slice() {
+            let input = r#"type MyFoo struct {
+	Names *[]string `json:"names"`
+	Aliases *[]string `json:"aliases,omitempty"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    pub names: Option<Vec<String>>,
+    #[serde(default)]
+    pub aliases: Option<Vec<String>>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_emit_enum_all() {
+            let input = r#"type Status string
+
+const (
+	StatusActive Status = "Active"
+	StatusInactive Status = "Inactive"
+	StatusDeleted Status = "Deleted"
+)"#;
+
+            let config = CodegenConfig {
+                string_enums: true,
+                emit_enum_all: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum Status {
+    Active,
+    Inactive,
+    Deleted,
+}
+
+impl Status {
+    pub const ALL: &'static [Status] = &[Status::Active, Status::Inactive, Status::Deleted];
+}"#
+            );
+        }
+
+        #[test]
+        fn test_map_of_interfaces() {
+            let input = r#"type MyFoo struct {
+	Attributes map[string]interface{} `json:"attributes"`
+	Payload interface{} `json:"payload"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo<T1=Value, T2=Value>
+where T1: DeserializeOwned,
+      T1: Serialize,
+      T2: DeserializeOwned,
+      T2: Serialize,
+{
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    #[serde(bound="")]
+    pub attributes: HashMap<String, T1>,
+    #[serde(bound="")]
+    pub payload: T2,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_sorted_imports_are_grouped() {
+            let mut scope = Scope::new();
+            let first: HashSet<String> = [
+                "serde_json::Value",
+                "serde::ser::Serialize",
+                "serde::de::DeserializeOwned",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+            let second: HashSet<String> = ["serde::de::Deserializer", "serde_json::Value"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+            add_sorted_imports(&mut scope, &first);
+            add_sorted_imports(&mut scope, &second);
+
+            assert_eq!(
+                scope.to_string(),
+                r#"use serde::de::{DeserializeOwned, Deserializer};
+use serde::ser::Serialize;
+use serde_json::Value;
+"#
+            );
+        }
+
+        #[test]
+        fn test_xml_tags_are_ignored() {
+            let input = r#"type MyFoo struct {
+	Name string `json:"fullName,omitempty" xml:"name"`
+	Count int `xml:"count"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    #[serde(rename = "fullName")]
+    pub name: Option<String>,
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_ordered_floats() {
+            let input = r#"type MyFoo struct {
+	Latitude float64 `json:"latitude"`
+	Samples []float32 `json:"samples"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub latitude: f64,
+    pub samples: Vec<f64>,
+}"#
+            );
+
+            let config = CodegenConfig {
+                ordered_floats: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use ordered_float::OrderedFloat;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub latitude: OrderedFloat<f64>,
+    pub samples: Vec<OrderedFloat<f64>>,
+}"#
+            );
+
+            // Maps aren't `Hash`, nor is a struct holding one.
+            let input = r#"type MyFoo struct {
+	Latitude float64 `json:"latitude"`
+	Bar MyBar `json:"bar"`
+}
+
+type MyBar struct {
+	Tags map[string]float64 `json:"tags"`
+}"#;
+            assert_eq!(
+                translate(input, &config),
+                r#"use ordered_float::OrderedFloat;
+use custom_serde::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub latitude: OrderedFloat<f64>,
+    pub bar: MyBar,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBar {
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    pub tags: HashMap<String, OrderedFloat<f64>>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_section_banners() {
+            let input = r#"// --- S3 Events ---
+
+type MyFoo struct {
+	Name string `json:"name"`
+}
+
+// A regular comment
+
+// ==========
+
+type MyBar struct {
+	Count int `json:"count"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+
+// === S3 Events ===
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+// ===
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBar {
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_field_naming() {
+            let input = r#"type MyFoo struct {
+	FooBar string `json:"fooBar"`
+	Count int `json:"item-count"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    #[serde(rename = "fooBar")]
+    pub foo_bar: Option<String>,
+    #[serde(rename = "item-count")]
+    pub count: i64,
+}"#
+            );
+
+            let expected = r#"use custom_serde::*;
+
+#[allow(non_snake_case)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub fooBar: Option<String>,
+    #[serde(rename = "item-count")]
+    pub count: i64,
+}"#;
+            for naming in &[FieldNaming::Original, FieldNaming::CamelCase] {
+                let config = CodegenConfig {
+                    field_naming: *naming,
+                    ..Default::default()
+                };
+                assert_eq!(translate(input, &config), expected);
+            }
+        }
+
+        #[test]
+        fn test_defined_type_is_newtype() {
+            let input = "type UserID string";
+            assert_eq!(translate(input, &CodegenConfig::default()), "pub type UserID = String;");
+
+            let config = CodegenConfig {
+                defined_type_newtypes: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct UserId(pub String);"#
+            );
+
+            let input = r#"type my_type string
+
+type MyFoo struct {
+	Kind my_type `json:"kind"`
+}"#;
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct MyType(pub String);
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub kind: MyType,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_type_alias_with_assign() {
+            let input = r#"type Alias = Foo
+
+type Count = int
+
+type Stamp = time.Time"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use chrono::{DateTime, Utc};
+
+pub type Alias = Foo;
+
+pub type Count = i64;
+
+pub type Stamp = DateTime<Utc>;"#
+            );
+        }
+
+        #[test]
+        fn test_struct_hook() {
+            let input = r#"type MyFoo struct {
+	Bar int64 `json:"bar"`
+}"#;
+
+            let config = CodegenConfig {
+                struct_hook: Some(StructHook::new(|s| {
+                    s.repr("C");
+                })),
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[repr(C)]
+pub struct MyFoo {
+    pub bar: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_slice_of_interface_maps() {
+            let input = r#"type MyFoo struct {
+	Items []map[string]interface{} `json:"items"`
+}"#;
+
+            // Only top-level maps go through `deserialize_lambda_map`.
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo<T1=Value>
+where T1: DeserializeOwned,
+      T1: Serialize,
+{
+    #[serde(bound="")]
+    pub items: Vec<HashMap<String, T1>>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_struct_renames() {
+            let input = r#"type MyFoo struct {
+	Bar int64 `json:"bar"`
+}
+
+type MyBaz struct {
+	Qux int64 `json:"qux"`
+}"#;
+
+            let mut struct_renames = HashMap::new();
+            struct_renames.insert("MyFoo".to_string(), "foo".to_string());
+            let config = CodegenConfig {
+                struct_renames,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename = "foo")]
+pub struct MyFoo {
+    pub bar: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBaz {
+    pub qux: i64,
+}"#
+            );
         }
-    };
 
-    Ok(rust_type)
+        #[test]
+        fn test_bson_renames() {
+            let input = r#"type MyFoo struct {
+	ID string `json:"id" bson:"_id,omitempty"`
+	Owner int64 `json:"ownerId" bson:"owner_id"`
+	Count int64 `json:"count" bson:"count"`
+}"#;
+
+            assert!(!translate(input, &CodegenConfig::default()).contains("bson"));
+
+            let config = CodegenConfig {
+                bson_renames: true,
+                use_custom_serde: false,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    #[cfg_attr(feature = "bson", serde(rename = "_id"))]
+    pub id: Option<String>,
+    #[cfg_attr(not(feature = "bson"), serde(rename = "ownerId"))]
+    #[cfg_attr(feature = "bson", serde(rename = "owner_id"))]
+    pub owner: i64,
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_tolerant_numbers() {
+            let input = r#"type MyFoo struct {
+	Count int64 `json:"count"`
+	Size uint32 `json:"size"`
+	Limit *int `json:"limit"`
+	Ratio float64 `json:"ratio"`
+}"#;
+
+            let config = CodegenConfig {
+                tolerant_numbers: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_i64")]
+    pub count: i64,
+    #[serde(deserialize_with = "deserialize_lambda_u64")]
+    pub size: u64,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    pub ratio: f64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_redacted_debug() {
+            let input = r#"type MyFoo struct {
+	User string `json:"user"`
+	// The session token.
+	// rust:redact
+	Token string `json:"token"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+use std::fmt;
+
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub user: Option<String>,
+    /// The session token.
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub token: Option<String>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl fmt::Debug for MyFoo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MyFoo")
+            .field("user", &self.user)
+            .field("token", &format_args!("[redacted]"))
+            .finish()
+    }
+}"#
+            );
+        }
 
-    mod primitives {
-        use super::*;
+        #[test]
+        fn test_map_of_bytes() {
+            let input = r#"type MyFoo struct {
+	Blobs map[string][]byte `json:"blobs"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+use std::collections::HashMap;
+use super::super::encodings::Base64Data;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    pub blobs: HashMap<String, Base64Data>,
+}"#
+            );
+        }
 
         #[test]
-        fn test_parses_array() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "[]bool",
-                rule: Rule::array,
-                tokens: [
-                    array(0, 6, [
-                        primitive(2, 6, [
-                            boolean(2, 6),
-                        ]),
-                    ]),
-                ]
+        fn test_optional_struct_fields() {
+            let input = r#"type MyFoo struct {
+	Primary *Detail `json:"primary"`
+	Secondary Detail `json:"secondary,omitempty"`
+}"#;
+
+            // No custom deserializer, these aren't strings or maps.
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    pub primary: Option<Detail>,
+    #[serde(default)]
+    pub secondary: Option<Detail>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_anonymous_struct_names() {
+            let input = r#"type Foo struct {
+	Detail struct {
+		Name string `json:"name"`
+	} `json:"detail"`
+	BarDetail struct {
+		Size int64 `json:"size"`
+	} `json:"barDetail"`
+}
+
+type FooBar struct {
+	Detail *struct {
+		Count int64 `json:"count"`
+	} `json:"detail"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Foo {
+    pub detail: FooDetail,
+    #[serde(rename = "barDetail")]
+    pub bar_detail: FooBarDetail,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FooDetail {
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FooBarDetail {
+    pub size: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FooBar {
+    #[serde(default)]
+    pub detail: Option<FooBarDetail2>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FooBarDetail2 {
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_crate_visibility() {
+            let input = r#"type MyFoo struct {
+	Bar int64 `json:"bar"`
+	Empty struct{} `json:"empty"`
+}
+
+type Count int
+
+type Total = int"#;
+
+            let config = CodegenConfig {
+                visibility: Visibility::Crate,
+                defined_type_newtypes: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub(crate) struct MyFoo {
+    pub(crate) bar: i64,
+    pub(crate) empty: Empty,
+}
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "[]blah",
-                rule: Rule::array,
-                tokens: [
-                    array(0, 6, [
-                        ident(2, 6),
-                    ]),
-                ]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub(crate) struct Empty {}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub(crate) struct Count(pub(crate) i64);
+
+pub(crate) type Total = i64;"#
+            );
+
+            let config = CodegenConfig {
+                visibility: Visibility::Private,
+                ..Default::default()
             };
+            assert!(translate(input, &config).contains("struct MyFoo {\n    bar: i64,\n"));
         }
 
         #[test]
-        fn test_parses_map() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "map[string]interface{}",
-                rule: Rule::map,
-                tokens: [
-                    map(0, 22, [
-                        key_type(4, 10, [
-                            primitive(4, 10, [
-                                string(4, 10),
-                            ]),
-                        ]),
-                        value_type(11, 22, [
-                            interface(11, 22),
-                        ]),
-                    ]),
-                ]
+        fn test_folded_constants() {
+            let input = r#"const (
+	// Base retry count.
+	MaxRetries = 1
+	MaxAttempts int = MaxRetries + 1
+	Window = (MaxAttempts - 3) * 2
+	Unknown = Elsewhere + 1
+	Name = "skipped"
+)"#;
+
+            // Only generated on request.
+            assert_eq!(translate(input, &CodegenConfig::default()), "");
+
+            let config = CodegenConfig {
+                emit_constants: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"/// Base retry count.
+pub const MAX_RETRIES: i64 = 1;
+
+pub const MAX_ATTEMPTS: i64 = 2;
+
+pub const WINDOW: i64 = -2;"#
+            );
         }
-    }
 
-    mod directives {
-        use super::*;
+        #[test]
+        fn test_flatten_prefix() {
+            let input = r#"type MyFoo struct {
+	Bucket string `json:"bucket"`
+	// User metadata.
+	// rust:flatten_prefix x-amz-meta-
+	Metadata map[string]string
+}"#;
+
+            let output = translate(input, &CodegenConfig::default());
+            assert!(output.contains(
+                r#"    /// User metadata.
+    #[serde(flatten, with = "prefix_map_x_amz_meta")]
+    pub metadata: HashMap<String, String>,
+}"#
+            ));
+            assert!(output.contains("mod prefix_map_x_amz_meta {\n"));
+            assert!(output.contains("    const PREFIX: &str = \"x-amz-meta-\";\n"));
+        }
 
         #[test]
-        fn test_parses_package_def() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "package foo",
-                rule: Rule::package_def,
-                tokens: [
-                    package_def(0, 11, [
-                        ident(8, 11),
-                    ]),
-                ]
+        fn test_package_type_alias() {
+            let input = "type Foo = events.Bar";
+
+            assert_eq!(translate(input, &CodegenConfig::default()), "pub type Foo = Bar;");
+
+            let mut alias_import_paths = HashMap::new();
+            alias_import_paths.insert("events".to_string(), "super::events".to_string());
+            let config = CodegenConfig {
+                alias_import_paths,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                "use super::events::Bar;\n\npub type Foo = Bar;"
+            );
+
+            // Defined types still need a known mapping.
+            assert!(parse_go_string("type Foo events.Bar".to_string()).is_err());
         }
 
         #[test]
-        fn test_parses_struct_def() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "type MyFoo struct {}",
-                rule: Rule::struct_def,
-                tokens: [
-                    struct_def(0, 20, [
-                        struct_preamble(0, 17, [
-                            struct_name(5, 10, [
-                                ident(5, 10),
-                            ]),
-                        ]),
-                    ]),
-                ]
+        fn test_base64_accessors() {
+            let input = r#"type MyFoo struct {
+	Payload []byte `json:"payload"`
+	Signature []byte `json:"signature,omitempty"`
+	Count int64 `json:"count"`
+}"#;
+
+            let config = CodegenConfig {
+                base64_accessors: true,
+                ..Default::default()
             };
+            let output = translate(input, &config);
+            assert!(output.ends_with(
+                r#"impl MyFoo {
+    pub fn payload_bytes(&self) -> &[u8] {
+        &self.payload.0
+    }
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "type MyFoo struct { foo string }",
-                rule: Rule::struct_def,
-                tokens: [
-                    struct_def(0, 32, [
-                        struct_preamble(0, 17, [
-                            struct_name(5, 10, [
-                                ident(5, 10),
-                            ]),
-                        ]),
-                        struct_fields(20, 31, [
-                            struct_field(20, 31, [
-                                struct_field_decl(20, 30, [
-                                    ident(20, 23),
-                                    struct_field_type(24, 30, [
-                                        primitive(24, 30, [
-                                            string(24, 30)
-                                        ]),
-                                    ]),
-                                ]),
-                            ]),
-                        ]),
-                    ]),
-                ]
-            };
+    pub fn signature_bytes(&self) -> Option<&[u8]> {
+        self.signature.as_ref().map(|data| data.0.as_slice())
+    }
+}"#
+            ));
+            assert!(!output.contains("count_bytes"));
+            assert!(!translate(input, &CodegenConfig::default()).contains("impl MyFoo"));
+        }
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "type MyFoo struct { T1 }",
-                rule: Rule::struct_def,
-                tokens: [
-                    struct_def(0, 24, [
-                        struct_preamble(0, 17, [
-                            struct_name(5, 10, [
-                                ident(5, 10),
-                            ]),
-                        ]),
-                        struct_fields(20, 23, [
-                            struct_field(20, 23, [
-                                struct_field_decl(20, 22, [
-                                    struct_embedded_field(20, 22, [
-                                        ident(20, 22),
-                                    ]),
-                                ]),
-                            ]),
-                        ]),
-                    ]),
-                ]
+        #[test]
+        fn test_derive_ord() {
+            let config = CodegenConfig {
+                derive_ord: true,
+                ..Default::default()
             };
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "type MyFoo struct { *T1 }",
-                rule: Rule::struct_def,
-                tokens: [
-                    struct_def(0, 25, [
-                        struct_preamble(0, 17, [
-                            struct_name(5, 10, [
-                                ident(5, 10),
-                            ]),
-                        ]),
-                        struct_fields(20, 24, [
-                            struct_field(20, 24, [
-                                struct_field_decl(20, 23, [
-                                    pointer(20, 21),
-                                    struct_embedded_field(21, 23, [
-                                        ident(21, 23),
-                                    ]),
-                                ]),
-                            ]),
-                        ]),
-                    ]),
-                ]
+            let input = r#"type MyFoo struct {
+	Count int64 `json:"count"`
+	Sizes []uint32 `json:"sizes"`
+}
+
+type MyBar struct {
+	Foo MyFoo `json:"foo"`
+}"#;
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub count: i64,
+    pub sizes: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub struct MyBar {
+    pub foo: MyFoo,
+}"#
+            );
+
+            // Floats aren't `Ord`, and neither is a struct holding one.
+            let input = r#"type MyFoo struct {
+	Count int64 `json:"count"`
+	Ratio float64 `json:"ratio"`
+}
+
+type MyBar struct {
+	Foo MyFoo `json:"foo"`
+}"#;
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub count: i64,
+    pub ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBar {
+    pub foo: MyFoo,
+}"#
+            );
+
+            let config = CodegenConfig {
+                ordered_floats: true,
+                ..config
             };
+            assert!(translate(input, &config).contains("PartialOrd, Ord"));
+        }
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: r#"type MyFoo struct {
-                  foo string
-                  bar int
-                }"#,
-                rule: Rule::struct_def,
-                tokens: [
-                    struct_def(0, 92, [
-                        struct_preamble(0, 17, [
-                            struct_name(5, 10, [
-                                ident(5, 10),
-                            ]),
-                        ]),
-                        struct_fields(38, 74, [
-                            struct_field(38, 48, [
-                                struct_field_decl(38, 48, [
-                                    ident(38, 41),
-                                    struct_field_type(42, 48, [
-                                        primitive(42, 48, [
-                                            string(42, 48)
-                                        ]),
-                                    ]),
-                                ]),
-                            ]),
-                            struct_field(67, 74, [
-                                struct_field_decl(67, 74, [
-                                    ident(67, 70),
-                                    struct_field_type(71, 74, [
-                                        primitive(71, 74, [
-                                            int(71, 74)
-                                        ]),
-                                    ]),
-                                ]),
-                            ]),
-                        ]),
-                    ]),
-                ]
+        #[test]
+        fn test_sql_null_types() {
+            let input = r#"type MyFoo struct {
+	Name sql.NullString `json:"name"`
+	Count sql.NullInt64 `json:"count"`
+}"#;
+
+            let expected = translate(input, &CodegenConfig::default());
+            assert!(expected.starts_with(
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(with = "sql_null_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(with = "sql_null_int64")]
+    #[serde(default)]
+    pub count: Option<i64>,
+}
+
+mod sql_null_string {"#
+            ), "{}", expected);
+            assert!(expected.contains("#[serde(rename = \"String\", default)]\n        value: Option<String>,"));
+            assert!(expected.contains("\nmod sql_null_int64 {"));
+
+            let config = CodegenConfig {
+                sql_nulls_as_option: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub name: Option<String>,
+    pub count: Option<i64>,
+}"#
+            );
         }
 
         #[test]
-        fn test_parses_struct_field() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "EventVersion string",
-                rule: Rule::struct_field,
-                tokens: [
-                    struct_field(0, 19, [
-                        struct_field_decl(0, 19, [
-                            ident(0, 12),
-                            struct_field_type(13, 19, [
-                                primitive(13, 19, [
-                                    string(13, 19),
-                                ]),
-                            ]),
-                        ]),
-                    ]),
-                ]
+        fn test_shared_fields() {
+            let input = r#"type MyFoo struct {
+	Bar MyBar `json:"bar"`
+	Baz *MyBar `json:"baz"`
+}"#;
+
+            let mut shared_fields = HashMap::new();
+            shared_fields.insert(FieldPath::new("MyFoo", "Bar"), SharedPointer::Arc);
+            shared_fields.insert(FieldPath::new("MyFoo", "Baz"), SharedPointer::Arc);
+            let config = CodegenConfig {
+                shared_fields,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub bar: Arc<MyBar>,
+    #[serde(default)]
+    pub baz: Option<Arc<MyBar>>,
+}"#
+            );
+        }
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "EventVersion bool",
-                rule: Rule::struct_field,
-                tokens: [
-                    struct_field(0, 17, [
-                        struct_field_decl(0, 17, [
-                            ident(0, 12),
-                            struct_field_type(13, 17, [
-                                primitive(13, 17, [
-                                    boolean(13, 17),
-                                ]),
-                            ]),
-                        ]),
-                    ]),
-                ]
+        #[test]
+        fn test_allow_unused() {
+            let input = r#"type MyFoo struct {
+	Name string `json:"name"`
+}"#;
+
+            let config = CodegenConfig {
+                allow_unused_imports: true,
+                ..Default::default()
             };
+            assert!(translate(input, &config).starts_with(
+                "#![allow(unused_imports)]\n\nuse custom_serde::*;\n\n#[derive("
+            ));
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "EventVersion *bool",
-                rule: Rule::struct_field,
-                tokens: [
-                    struct_field(0, 18, [
-                        struct_field_decl(0, 18, [
-                            ident(0, 12),
-                            pointer(13, 14),
-                            struct_field_type(14, 18, [
-                                primitive(14, 18, [
-                                    boolean(14, 18),
-                                ]),
-                            ]),
-                        ]),
-                    ]),
-                ]
+            let config = CodegenConfig {
+                allow_dead_code: true,
+                ..config
             };
+            assert!(translate(input, &config).starts_with("#![allow(unused_imports, dead_code)]\n\nuse "));
         }
 
         #[test]
-        fn test_parses_struct_embedded_field() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "EventVersion",
-                rule: Rule::struct_field,
-                tokens: [
-                    struct_field(0, 12, [
-                        struct_field_decl(0, 12, [
-                            struct_embedded_field(0, 12, [
-                                ident(0, 12),
-                            ]),
-                        ]),
-                    ]),
-                ]
+        fn test_pointer_to_map() {
+            let input = r#"type MyFoo struct {
+	Labels *map[string]string `json:"labels,omitempty"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    pub labels: Option<HashMap<String, String>>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_serde_with() {
+            let input = r#"type MyFoo struct {
+	Count int64 `json:"count,string"`
+	Limit *int64 `json:"limit,string"`
+	Name string `json:"name,string"`
+}"#;
+
+            let config = CodegenConfig {
+                use_serde_with: true,
+                tolerant_numbers: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+use serde_with::{DisplayFromStr, serde_as};
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde_as(as = "DisplayFromStr")]
+    pub count: i64,
+    #[serde(default)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub limit: Option<i64>,
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+}"#
+            );
+
+            // The option is ignored otherwise.
+            assert!(!translate(input, &CodegenConfig::default()).contains("serde_as"));
+        }
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "*EventVersion",
-                rule: Rule::struct_field,
-                tokens: [
-                    struct_field(0, 13, [
-                        struct_field_decl(0, 13, [
-                            pointer(0, 1),
-                            struct_embedded_field(1, 13, [
-                                ident(1, 13),
-                            ]),
-                        ]),
-                    ]),
-                ]
+        #[test]
+        fn test_byte_order_mark() {
+            let input = "package events\n\ntype MyFoo struct {\n\tName string `json:\"name\"`\n}";
+            let with_bom = format!("\u{feff}{}", input);
+            assert_eq!(
+                translate(&with_bom, &CodegenConfig::default()),
+                translate(input, &CodegenConfig::default())
+            );
+        }
+
+        #[test]
+        fn test_uuids() {
+            let input = r#"type MyFoo struct {
+	ID uuid.UUID `json:"id"`
+	ParentID *uuid.UUID `json:"parentId"`
+}"#;
+
+            let config = CodegenConfig {
+                uuids: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub id: Uuid,
+    #[serde(default)]
+    #[serde(rename = "parentId")]
+    pub parent_id: Option<Uuid>,
+}"#
+            );
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub id: String,
+    #[serde(default)]
+    #[serde(rename = "parentId")]
+    pub parent_id: Option<String>,
+}"#
+            );
+        }
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "EventVersion MyType",
-                rule: Rule::struct_field,
-                tokens: [
-                    struct_field(0, 19, [
-                        struct_field_decl(0, 19, [
-                            ident(0, 12),
-                            struct_field_type(13, 19, [
-                                ident(13, 19),
-                            ]),
-                        ]),
-                    ]),
-                ]
+        #[test]
+        fn test_strings_as_boxed_str() {
+            let input = r#"type MyFoo struct {
+	Name string `json:"name"`
+	Nickname string `json:"nickname,omitempty"`
+	Region string `json:"region" default:"us-east-1"`
+	Tags []string `json:"tags"`
+}"#;
+
+            let config = CodegenConfig {
+                strings_as_boxed_str: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_boxed_str")]
+    #[serde(default)]
+    pub name: Option<Box<str>>,
+    #[serde(default)]
+    pub nickname: Option<Box<str>>,
+    #[serde(deserialize_with = "deserialize_lambda_boxed_str")]
+    #[serde(default = "default_my_foo_region")]
+    pub region: Option<Box<str>>,
+    pub tags: Vec<String>,
+}
+
+fn default_my_foo_region() -> Option<Box<str>> {
+    Some("us-east-1".into())
+}"#
+            );
         }
 
         #[test]
-        fn test_parses_json_mapping() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "`json:\"fooBar\"`",
-                rule: Rule::json_mapping,
-                tokens: [
-                    json_mapping(0, 15, [
-                        json_name(7, 13),
-                    ]),
-                ]
+        fn test_dotted_json_names() {
+            let input = r#"type MyFoo struct {
+	Count int64 `json:"a.b,omitempty"`
+}"#;
+
+            // The key is taken literally, serde doesn't nest on dots either.
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    #[serde(rename = "a.b")]
+    pub count: Option<i64>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_map_types() {
+            let input = r#"type MyFoo struct {
+	Tags map[string]string `json:"tags"`
+}"#;
+
+            for &(map_type, import, name) in &[
+                (MapType::HashMap, "std::collections::HashMap", "HashMap"),
+                (MapType::BTreeMap, "std::collections::BTreeMap", "BTreeMap"),
+                (MapType::IndexMap, "indexmap::IndexMap", "IndexMap"),
+            ] {
+                let config = CodegenConfig {
+                    map_type,
+                    ..Default::default()
+                };
+                assert_eq!(
+                    translate(input, &config),
+                    format!(
+                        r#"use custom_serde::*;
+use {};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {{
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    pub tags: {}<String, String>,
+}}"#,
+                        import, name
+                    )
+                );
+            }
+        }
+
+        #[test]
+        fn test_skips_context_fields() {
+            let input = r#"import "context"
+
+type MyFoo struct {
+	ctx context.Context
+	Name string `json:"name"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_emit_layout_modules() {
+            let input = r#"type MyFoo struct {
+	// layout: 2006-01-02
+	Start time.Time `json:"start"`
+	// layout: 2006-01-02
+	End *time.Time `json:"end"`
+}"#;
+
+            let config = CodegenConfig {
+                emit_layout_modules: true,
+                ..Default::default()
             };
+            let expected = translate(input, &config);
+            assert!(expected.starts_with(
+                r#"use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    /// layout: 2006-01-02
+    /// Serialized with the `%Y-%m-%d` chrono format.
+    #[serde(with = "layout_2006_01_02")]
+    pub start: DateTime<Utc>,
+    /// layout: 2006-01-02
+    /// Serialized with the `%Y-%m-%d` chrono format.
+    #[serde(with = "layout_2006_01_02::option")]
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+}
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "`json:\"foo-x\"`",
-                rule: Rule::json_mapping,
-                tokens: [
-                    json_mapping(0, 14, [
-                        json_name(7, 12),
-                    ]),
-                ]
+// A field may only need one of the functions.
+#[allow(dead_code)]
+mod layout_2006_01_02 {"#
+            ), "{}", expected);
+            assert_eq!(expected.matches("mod layout_").count(), 1);
+            assert!(expected.contains("const FORMAT: &str = \"%Y-%m-%d\";"));
+            assert!(expected.contains("\n    pub mod option {"));
+        }
+
+        #[test]
+        fn test_slice_of_string_pointers() {
+            let input = r#"type MyFoo struct {
+	Names []*string `json:"names"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub names: Vec<Option<String>>,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_remote_timestamp_shims() {
+            let input = r#"type MyFoo struct {
+	Start MilliSecondsEpochTime `json:"start"`
+	End MilliSecondsEpochTime `json:"end"`
+	At SecondsEpochTime `json:"at"`
+}"#;
+
+            let config = CodegenConfig {
+                remote_timestamp_shims: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"use chrono::{DateTime, Utc};
+use custom_serde::*;
+use super::super::encodings::{MillisecondTimestamp, SecondTimestamp};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(with = "MillisecondTimestampDef")]
+    pub start: MillisecondTimestamp,
+    #[serde(with = "MillisecondTimestampDef")]
+    pub end: MillisecondTimestamp,
+    #[serde(with = "SecondTimestampDef")]
+    pub at: SecondTimestamp,
+}
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "`json:\"foo.x\"`",
-                rule: Rule::json_mapping,
-                tokens: [
-                    json_mapping(0, 14, [
-                        json_name(7, 12),
-                    ]),
-                ]
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "MillisecondTimestamp")]
+struct MillisecondTimestampDef(
+    #[serde(deserialize_with = "deserialize_milliseconds")]
+    #[serde(serialize_with = "serialize_milliseconds")]
+    pub DateTime<Utc>,
+);
+
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "SecondTimestamp")]
+struct SecondTimestampDef(
+    #[serde(deserialize_with = "deserialize_seconds")]
+    #[serde(serialize_with = "serialize_seconds")]
+    pub DateTime<Utc>,
+);"#
+            );
+        }
+
+        #[test]
+        fn test_struct_alias() {
+            let input = r#"type Point = struct { X, Y int }"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_asymmetric_rename() {
+            let input = r#"type MyFoo struct {
+	// rust:rename_ser newName // rust:rename_de oldName
+	Name int `json:"name"`
+	// The count.
+	// rust:rename_de total
+	Count int `json:"count"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(rename(serialize = "newName", deserialize = "oldName"))]
+    pub name: i64,
+    /// The count.
+    #[serde(rename(serialize = "count", deserialize = "total"))]
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_platform_int_bits() {
+            let input = r#"type MyFoo struct {
+	Count int `json:"count"`
+	Size uint `json:"size"`
+	Total int64 `json:"total"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub count: i64,
+    pub size: u64,
+    pub total: i64,
+}"#
+            );
+
+            let config = CodegenConfig {
+                platform_int_bits: 32,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub count: i32,
+    pub size: u32,
+    pub total: i64,
+}"#
+            );
+        }
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "`json:\"foo,omitempty\"`",
-                rule: Rule::json_mapping,
-                tokens: [
-                    json_mapping(0, 22, [
-                        json_name(7, 10),
-                        omit_empty(10, 20),
-                    ]),
-                ]
+        #[test]
+        fn test_example_tag() {
+            let input = r#"type MyFoo struct {
+	// The number.
+	N int `json:"n" example:"42"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    /// The number.
+    ///
+    /// # Example
+    ///
+    /// `42`
+    pub n: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_generate_validate() {
+            let input = r#"type MyFoo struct {
+	Name string `json:"name" validate:"required"`
+	Code string `json:"code" validate:"max=5,alpha"`
+	Count int `json:"count" validate:"min=1"`
+}"#;
+
+            let config = CodegenConfig {
+                generate_validate: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"use custom_serde::*;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    /// Validation: required
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Validation: max=5,alpha
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub code: Option<String>,
+    /// Validation: min=1
+    pub count: i64,
+}
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "`json:\"fooBar\"` // whatever",
-                rule: Rule::json_mapping,
-                tokens: [
-                    json_mapping(0, 27, [
-                        json_name(7, 13),
-                        any_comment(16, 27),
-                    ]),
-                ]
+/// A field that fails a constraint of its `validate` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub constraint: &'static str,
+}
+
+impl MyFoo {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.name.is_none() {
+            return Err(ValidationError { field: "name", constraint: "required" });
+        }
+        if let Some(ref value) = self.code {
+            if value.chars().count() > 5 {
+                return Err(ValidationError { field: "code", constraint: "max=5" });
+            }
+        }
+        if self.count < 1 {
+            return Err(ValidationError { field: "count", constraint: "min=1" });
+        }
+        Ok(())
+    }
+}"#
+            );
+        }
+
+        #[test]
+        fn test_raw_message_slice() {
+            let input = r#"type MyFoo struct {
+	Records []json.RawMessage `json:"records"`
+}"#;
+
+            let config = CodegenConfig {
+                interfaces_as_value: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub records: Vec<Value>,
+}"#
+            );
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use serde::de::DeserializeOwned;
+use serde::ser::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo<T1=Value>
+where T1: DeserializeOwned,
+      T1: Serialize,
+{
+    #[serde(bound="")]
+    pub records: Vec<T1>,
+}"#
+            );
         }
 
         #[test]
-        fn test_parses_import() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "import \"foo\"",
-                rule: Rule::import,
-                tokens: [
-                    import(0, 12, [
-                        import_package(7, 12, [
-                            package_name(8, 11),
-                        ]),
-                    ]),
-                ]
+        fn test_doc_overrides() {
+            let input = r#"// MyFoo is marshaled by the Go runtime.
+type MyFoo struct {
+	Count int `json:"count"`
+}
+
+// MyBar is a bar.
+type MyBar struct {
+	Count int `json:"count"`
+}"#;
+
+            let mut config = CodegenConfig::default();
+            config
+                .doc_overrides
+                .insert("MyFoo".to_string(), "A foo, see the docs.".to_string());
+            assert_eq!(
+                translate(input, &config),
+                r#"/// A foo, see the docs.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    pub count: i64,
+}
+
+/// `MyBar` is a bar.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyBar {
+    pub count: i64,
+}"#
+            );
+        }
+
+        #[test]
+        fn test_generic_type_alias() {
+            let input = r#"type List[T any] []T
+
+type Grid[T comparable] = [][]T"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"pub type List<T> = Vec<T>;
+
+pub type Grid<T> = Vec<Vec<T>>;"#
+            );
+        }
+
+        #[test]
+        fn test_omitempty_slice_aliases() {
+            let input = r#"type IDs []string
+
+type MyFoo struct {
+	Ids IDs `json:"ids,omitempty"`
+	Others IDs `json:"others"`
+}"#;
+
+            let config = CodegenConfig {
+                omitempty_slice_aliases: true,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"pub type IDs = Vec<String>;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MyFoo {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ids: IDs,
+    pub others: IDs,
+}"#
+            );
+
+            assert!(translate(input, &CodegenConfig::default()).contains("pub ids: Option<IDs>,"));
+        }
 
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "import \"a/b\"",
-                rule: Rule::import,
-                tokens: [
-                    import(0, 12, [
-                        import_package(7, 12, [
-                            package_name(8, 11),
-                        ]),
-                    ]),
-                ]
+        #[test]
+        fn test_max_nesting_depth() {
+            // Deep enough to overflow the stack if it reached the parser.
+            for prefix in &["[]", "map[string]", "*"] {
+                let input = format!("type Foo struct {{\n  Bar {}string\n}}", prefix.repeat(100_000));
+                let err = parse_go_string(input).expect_err("nesting limit to be enforced");
+                assert!(err.to_string().contains("maximum depth of 64"));
+            }
+
+            let config = CodegenConfig {
+                max_nesting_depth: 2,
+                ..Default::default()
             };
+            let input = "type Foo struct {\n  Bar [][]string\n}";
+            assert!(parse_go_string_with_config(input.to_string(), &config).is_ok());
+            let input = "type Foo struct {\n  Bar [][][]string\n}";
+            assert!(parse_go_string_with_config(input.to_string(), &config).is_err());
         }
 
         #[test]
-        fn test_parses_mutiple_imports() {
-            parses_to! {
-                parser: AwsGoEventsParser,
-                input: "import (\n\"foo\"\n \"bar\"\n)",
-                rule: Rule::import_multiple,
-                tokens: [
-                    import_multiple(0, 23, [
-                        import_package(9, 14, [
-                            package_name(10, 13),
-                        ]),
-                        import_package(16, 21, [
-                            package_name(17, 20),
-                        ]),
-                    ]),
-                ]
+        fn test_custom_serde() {
+            let input = r#"type Foo struct {
+  Name string `json:"name"`
+  Tags map[string]string `json:"tags"`
+}"#;
+
+            assert_eq!(
+                translate(input, &CodegenConfig::default()),
+                r#"use custom_serde::*;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Foo {
+    #[serde(deserialize_with = "deserialize_lambda_string")]
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(deserialize_with = "deserialize_lambda_map")]
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}"#
+            );
+
+            let config = CodegenConfig {
+                use_custom_serde: false,
+                ..Default::default()
             };
+            assert_eq!(
+                translate(input, &config),
+                r#"use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Foo {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}"#
+            );
         }
     }
 }
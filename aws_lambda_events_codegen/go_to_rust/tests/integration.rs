@@ -5,11 +5,15 @@ extern crate rustc_test as test;
 
 use glob::glob;
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
 use std::io::BufReader;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
 use test::{DynTestFn, DynTestName, TestDesc, TestDescAndFn};
 
 fn mk_test(desc: &str, input: String, expect: String, expected_path: PathBuf) -> TestDescAndFn {
@@ -35,6 +39,29 @@ fn mk_test(desc: &str, input: String, expect: String, expected_path: PathBuf) ->
     }
 }
 
+fn mk_error_test(desc: &str, input: String, expect: String) -> TestDescAndFn {
+    TestDescAndFn {
+        desc: TestDesc::new(DynTestName(desc.to_string())),
+        testfn: DynTestFn(Box::new(move || {
+            match go_to_rust::parse_go_string(input.clone()) {
+                Err(e) => assert_eq!(e.to_string(), expect),
+                Ok((_, output)) => panic!(
+                    "\n- input -\n{}\n- got -\n{}\n- expected error -\n{}\n",
+                    input, output, expect
+                ),
+            }
+        })),
+    }
+}
+
+fn read_fixture(p: &Path) -> String {
+    let file = File::open(p).unwrap();
+    let mut reader = BufReader::new(file);
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+    text.trim().to_string()
+}
+
 fn tests(src_dir: &Path) -> Vec<TestDescAndFn> {
     let mut tests = vec![];
 
@@ -49,26 +76,27 @@ fn tests(src_dir: &Path) -> Vec<TestDescAndFn> {
         let fixture_path = entry.unwrap();
         let input_path = fixture_path.clone().join("input.txt");
         let expected_path = fixture_path.clone().join("expected.txt");
-
-        let data: Vec<String> = vec![input_path.clone(), expected_path.clone()]
-            .iter()
-            .map(|p| {
-                let file = File::open(&p).unwrap();
-                let mut reader = BufReader::new(file);
-                let mut text = String::new();
-                reader.read_to_string(&mut text).unwrap();
-                text
-            }).collect();
-
         let test_name = fixture_path
             .file_stem()
             .expect("fixture to have a file stem")
-            .to_string_lossy();
+            .to_string_lossy()
+            .to_string();
+
+        // Inputs that can't be translated expect an error instead.
+        let error_path = fixture_path.clone().join("expected_error.txt");
+        if error_path.exists() {
+            tests.push(mk_error_test(
+                &test_name,
+                read_fixture(&input_path),
+                read_fixture(&error_path),
+            ));
+            continue;
+        }
 
         tests.push(mk_test(
             &test_name,
-            data[0].clone().trim().to_string(),
-            data[1].clone().trim().to_string(),
+            read_fixture(&input_path),
+            read_fixture(&expected_path),
             expected_path.clone(),
         ));
     }
@@ -76,7 +104,42 @@ fn tests(src_dir: &Path) -> Vec<TestDescAndFn> {
     tests
 }
 
+fn generate_to_path_test(src_dir: &Path) -> TestDescAndFn {
+    let input = src_dir.join("tests/fixtures/struct_members/input.txt");
+    TestDescAndFn {
+        desc: TestDesc::new(DynTestName("generate_to_path_skips_unchanged".to_string())),
+        testfn: DynTestFn(Box::new(move || {
+            let config = go_to_rust::CodegenConfig::default();
+            // Unique per run, so concurrent runs don't share the file.
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("time after the epoch")
+                .subsec_nanos();
+            let dir = env::temp_dir().join(format!("go_to_rust_{}_{}", process::id(), nanos));
+            fs::create_dir_all(&dir).expect("dir to be created");
+            let dest = dir.join("generated.rs");
+
+            assert!(go_to_rust::generate_to_path(&input, &dest, &config).expect("file to be generated"));
+            let written = fs::read_to_string(&dest).expect("file to be read");
+            #[cfg(unix)]
+            let inode = fs::metadata(&dest).expect("metadata").ino();
+
+            assert!(!go_to_rust::generate_to_path(&input, &dest, &config).expect("file to be generated"));
+            assert_eq!(written, fs::read_to_string(&dest).expect("file to be read"));
+            // A rewrite renames a new file into place.
+            #[cfg(unix)]
+            assert_eq!(inode, fs::metadata(&dest).expect("metadata").ino());
+            assert_eq!(fs::read_dir(&dir).expect("dir to be read").count(), 1);
+
+            fs::remove_dir_all(&dir).expect("dir to be removed");
+        })),
+    }
+}
+
 fn main() {
     let args: Vec<_> = env::args().collect();
-    test::test_main(&args, tests(Path::new(env!("CARGO_MANIFEST_DIR"))));
+    let src_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut tests = tests(src_dir);
+    tests.push(generate_to_path_test(src_dir));
+    test::test_main(&args, tests);
 }